@@ -1,24 +1,57 @@
-use crate::repository::{
-    object::Object,
-    repo::{RepoBranchFile, RepoData},
+use crate::{
+    config::Config,
+    repository::{
+        backend::{self, GitBackend},
+        last_commit::LastCommits,
+        object::Object,
+        path_index::PathIndex,
+        repo::Repo,
+        repo_list::RepoListCache,
+    },
+    utils::{cache::ByteCache, request_id},
 };
+use actix_web::{http::StatusCode, HttpResponse, ResponseError};
+use serde::Serialize;
+use std::fmt;
 use std::sync::Arc;
-use std::{collections::HashMap, fmt};
-use tokio::sync::Mutex;
-
-/// The filesystem path where public repositories live
-pub const REPOS_PATH: &str = "/home/git/repos/public/";
 
 /// A model for the applications state
 pub struct AppState {
-    /// The maximum size of a payload the application should accept
-    pub max_payload: i64,
-    /// The `Object` cache which is accessable using a hash key
-    pub object_hash_cache: HashMap<String, Object>,
-    /// The `Repo` cache which is accessable using a hash key
-    pub repo_hash_cache: HashMap<String, Vec<RepoBranchFile>>,
+    /// The resolved configuration of the application
+    pub config: Config,
+    /// How repositories are read
+    pub backend: Arc<dyn GitBackend>,
+    /// The index of the paths objects live at in each repository
+    pub path_index: PathIndex,
+    /// The `Object` cache keyed by repository and full object id
+    pub object_cache: ByteCache<Object>,
+    /// The `Repo` (tree) cache keyed by repository and full object id,
+    /// along with the revision and path its last commits were found from
+    /// when it has them
+    pub tree_cache: ByteCache<Repo>,
+    /// The `LastCommits` cache keyed by repository, tree id, revision and
+    /// path
+    pub last_commit_cache: ByteCache<LastCommits>,
     /// A cache of the `RepoData` (name, description, and last commit)
-    pub repos_cache: Arc<Mutex<Vec<RepoData>>>,
+    pub repos_cache: RepoListCache,
+}
+impl AppState {
+    /// Create the application state with empty caches.
+    pub fn new(config: Config) -> AppState {
+        AppState {
+            backend: backend::from_kind(config.git.backend),
+            path_index: PathIndex::default(),
+            object_cache: ByteCache::new(config.cache.object_cache_bytes),
+            tree_cache: ByteCache::new(config.cache.tree_cache_bytes),
+            last_commit_cache: ByteCache::new(config.cache.last_commit_cache_bytes),
+            repos_cache: RepoListCache::new(
+                &config.repos.roots,
+                config.cache.repo_list_ttl(),
+                config.cache.watch_repos,
+            ),
+            config,
+        }
+    }
 }
 
 #[derive(Debug)]
@@ -28,13 +61,19 @@ pub enum GitApiError {
     NoLastElement,
 
     /// GitApiError involving a failed process command
-    CommandFailed,
+    CommandFailed(GitCommandFailure),
 
-    /// GitApiError involving a failed cwd change
-    DirectoryChangeError,
+    /// A git command was given a revision that doesn't exist
+    UnknownRevision(GitCommandFailure),
 
-    /// GitApiError involving invalid UTF-8
-    InvalidUtf8,
+    /// A git command was run against a directory that isn't a repository
+    NotAGitRepository(GitCommandFailure),
+
+    /// A git command was given an object that doesn't exist or is corrupt
+    BadObject(GitCommandFailure),
+
+    /// A git command couldn't tell if an argument was a revision or a path
+    AmbiguousArgument(GitCommandFailure),
 
     /// GitApiError -> std::io::Error
     StdIoError(std::io::Error),
@@ -47,6 +86,101 @@ pub enum GitApiError {
 
     /// Invalid user input.
     InvalidInput(String),
+
+    /// The requested repository does not exist on the server.
+    RepoNotFound(String),
+
+    /// The requested object could not be found in the repository.
+    ObjectNotFound(String),
+
+    /// A git command ran longer than allowed and was killed.
+    CommandTimedOut(Vec<String>),
+
+    /// The request ran out of its time budget.
+    RequestTimedOut,
+
+    /// Too many git commands are running to take on more work.
+    Overloaded,
+
+    /// The git backend failed in a way not covered by other errors.
+    Backend(String),
+}
+impl GitApiError {
+    /// A stable, machine readable code identifying the kind of error.
+    pub fn code(&self) -> &'static str {
+        match self {
+            GitApiError::NoLastElement => "no_last_element",
+            GitApiError::CommandFailed(_) => "git_command_failed",
+            GitApiError::UnknownRevision(_) => "unknown_revision",
+            GitApiError::NotAGitRepository(_) => "not_a_git_repository",
+            GitApiError::BadObject(_) => "bad_object",
+            GitApiError::AmbiguousArgument(_) => "ambiguous_argument",
+            GitApiError::FromUtf8(_) => "invalid_utf8",
+            GitApiError::StdIoError(_) => "io_error",
+            GitApiError::RepoWithNoCommits(_) => "repo_with_no_commits",
+            GitApiError::InvalidInput(_) => "invalid_input",
+            GitApiError::RepoNotFound(_) => "repo_not_found",
+            GitApiError::ObjectNotFound(_) => "object_not_found",
+            GitApiError::CommandTimedOut(_) => "git_command_timed_out",
+            GitApiError::RequestTimedOut => "request_timed_out",
+            GitApiError::Overloaded => "overloaded",
+            GitApiError::Backend(_) => "backend_error",
+        }
+    }
+
+    /// A human readable message that is safe to show to clients.
+    ///
+    /// Unlike the `Display` implementation this never includes
+    /// server paths, command arguments or raw git output.
+    pub fn public_message(&self) -> String {
+        match self {
+            GitApiError::InvalidInput(_)
+            | GitApiError::RepoNotFound(_)
+            | GitApiError::ObjectNotFound(_) => self.to_string(),
+            GitApiError::RepoWithNoCommits(_) => "Repository has no commits".to_string(),
+            GitApiError::CommandTimedOut(_) | GitApiError::RequestTimedOut => {
+                "The request took too long to process".to_string()
+            }
+            GitApiError::Overloaded => "The server is busy, try again later".to_string(),
+            GitApiError::UnknownRevision(_) => "Unknown revision".to_string(),
+            GitApiError::NotAGitRepository(_) => "Not a git repository".to_string(),
+            GitApiError::BadObject(_) => "Bad object".to_string(),
+            GitApiError::AmbiguousArgument(_) => {
+                "Unknown revision or path in repository".to_string()
+            }
+            _ => self
+                .status_code()
+                .canonical_reason()
+                .unwrap_or("Internal Server Error")
+                .to_string(),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+/// Details about a git command that exited unsuccessfully
+pub struct GitCommandFailure {
+    /// The arguments git was invoked with
+    pub args: Vec<String>,
+    /// The exit code, `None` if the process was killed by a signal
+    pub status: Option<i32>,
+    /// The trimmed standard error output of the command
+    pub stderr: String,
+}
+impl fmt::Display for GitCommandFailure {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let status = self
+            .status
+            .map(|code| format!("exit code {code}"))
+            .unwrap_or_else(|| "a signal".to_string());
+
+        write!(f, "`git {}` terminated by {status}", self.args.join(" "))?;
+        if !self.stderr.is_empty() {
+            write!(f, ": {}", self.stderr)?;
+        }
+
+        Ok(())
+    }
 }
 // Implement display trait for RevereGitApiError
 impl fmt::Display for GitApiError {
@@ -54,9 +188,22 @@ impl fmt::Display for GitApiError {
         match self {
             GitApiError::RepoWithNoCommits(s) => write!(f, "Uh oh, repo with no commits: {s}"),
             GitApiError::InvalidInput(s) => write!(f, "Invalid input: {s}"),
-
-            // TODO: Think of more meaningful display messages
-            _ => write!(f, "error"),
+            GitApiError::RepoNotFound(s) => write!(f, "Repository not found: {s}"),
+            GitApiError::ObjectNotFound(s) => write!(f, "Object not found: {s}"),
+            GitApiError::StdIoError(e) => write!(f, "I/O error: {e}"),
+            GitApiError::FromUtf8(e) => write!(f, "Invalid UTF-8: {e}"),
+            GitApiError::NoLastElement => write!(f, "Expected at least one element, found none"),
+            GitApiError::CommandFailed(c) => write!(f, "Git command failed: {c}"),
+            GitApiError::UnknownRevision(c) => write!(f, "Unknown revision: {c}"),
+            GitApiError::NotAGitRepository(c) => write!(f, "Not a git repository: {c}"),
+            GitApiError::BadObject(c) => write!(f, "Bad object: {c}"),
+            GitApiError::AmbiguousArgument(c) => write!(f, "Ambiguous argument: {c}"),
+            GitApiError::CommandTimedOut(args) => {
+                write!(f, "`git {}` timed out and was killed", args.join(" "))
+            }
+            GitApiError::RequestTimedOut => write!(f, "Request ran out of time"),
+            GitApiError::Overloaded => write!(f, "Too many git commands running"),
+            GitApiError::Backend(s) => write!(f, "Git backend error: {s}"),
         }
     }
 }
@@ -70,3 +217,165 @@ impl From<std::string::FromUtf8Error> for GitApiError {
         GitApiError::FromUtf8(err)
     }
 }
+impl From<GitCommandFailure> for GitApiError {
+    /// Classify a failed git command by the error git reported.
+    fn from(failure: GitCommandFailure) -> GitApiError {
+        let stderr = failure.stderr.to_lowercase();
+
+        if stderr.contains("ambiguous argument") {
+            GitApiError::AmbiguousArgument(failure)
+        } else if stderr.contains("not a git repository") {
+            GitApiError::NotAGitRepository(failure)
+        } else if stderr.contains("bad object")
+            || stderr.contains("bad file")
+            || stderr.contains("not a tree object")
+        {
+            GitApiError::BadObject(failure)
+        } else if stderr.contains("unknown revision")
+            || stderr.contains("bad revision")
+            || stderr.contains("not a valid object name")
+            || stderr.contains("invalid object name")
+            || stderr.contains("needed a single revision")
+        {
+            GitApiError::UnknownRevision(failure)
+        } else {
+            GitApiError::CommandFailed(failure)
+        }
+    }
+}
+impl ResponseError for GitApiError {
+    fn status_code(&self) -> StatusCode {
+        match self {
+            GitApiError::InvalidInput(_) => StatusCode::BAD_REQUEST,
+            GitApiError::RepoNotFound(_)
+            | GitApiError::ObjectNotFound(_)
+            | GitApiError::UnknownRevision(_)
+            | GitApiError::NotAGitRepository(_)
+            | GitApiError::BadObject(_)
+            | GitApiError::AmbiguousArgument(_) => StatusCode::NOT_FOUND,
+            GitApiError::RepoWithNoCommits(_) => StatusCode::CONFLICT,
+            GitApiError::CommandFailed(_) => StatusCode::BAD_GATEWAY,
+            GitApiError::CommandTimedOut(_) | GitApiError::RequestTimedOut => {
+                StatusCode::GATEWAY_TIMEOUT
+            }
+            GitApiError::Overloaded => StatusCode::SERVICE_UNAVAILABLE,
+            GitApiError::NoLastElement
+            | GitApiError::StdIoError(_)
+            | GitApiError::FromUtf8(_)
+            | GitApiError::Backend(_) => StatusCode::INTERNAL_SERVER_ERROR,
+        }
+    }
+
+    fn error_response(&self) -> HttpResponse {
+        let status = self.status_code();
+        let request_id = request_id::current();
+
+        // Only log the details of server side failures, client errors
+        // are expected and would just be noise in the logs.
+        if status.is_server_error() {
+            log::error!("[{}] {self}", request_id.as_deref().unwrap_or("-"));
+        } else {
+            log::debug!("[{}] {self}", request_id.as_deref().unwrap_or("-"));
+        }
+
+        HttpResponse::build(status).json(ErrorEnvelope {
+            error: ErrorBody {
+                code: self.code(),
+                message: self.public_message(),
+                request_id,
+            },
+        })
+    }
+}
+
+#[derive(Serialize, Debug)]
+/// The JSON envelope every error response is wrapped in
+pub struct ErrorEnvelope {
+    pub error: ErrorBody,
+}
+
+#[derive(Serialize, Debug)]
+#[serde(rename_all = "camelCase")]
+/// The body of an error response
+pub struct ErrorBody {
+    pub code: &'static str,
+    pub message: String,
+    pub request_id: Option<String>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use actix_web::body::to_bytes;
+
+    #[test]
+    fn test_error_status_codes() {
+        assert_eq!(
+            GitApiError::InvalidInput("bad".into()).status_code(),
+            StatusCode::BAD_REQUEST
+        );
+        assert_eq!(
+            GitApiError::RepoNotFound("repo".into()).status_code(),
+            StatusCode::NOT_FOUND
+        );
+        assert_eq!(
+            GitApiError::ObjectNotFound("abc123".into()).status_code(),
+            StatusCode::NOT_FOUND
+        );
+        assert_eq!(
+            GitApiError::RepoWithNoCommits("repo".into()).status_code(),
+            StatusCode::CONFLICT
+        );
+    }
+
+    fn failure(stderr: &str) -> GitCommandFailure {
+        GitCommandFailure {
+            args: vec!["log".into(), "nope".into()],
+            status: Some(128),
+            stderr: stderr.into(),
+        }
+    }
+
+    #[test]
+    fn test_classify_command_failure() {
+        let err = GitApiError::from(failure(
+            "fatal: ambiguous argument 'nope': unknown revision or path not in the working tree.",
+        ));
+        assert!(matches!(err, GitApiError::AmbiguousArgument(_)));
+        assert_eq!(err.status_code(), StatusCode::NOT_FOUND);
+
+        let err = GitApiError::from(failure("fatal: Not a valid object name nope"));
+        assert!(matches!(err, GitApiError::UnknownRevision(_)));
+
+        let err = GitApiError::from(failure("fatal: bad object deadbeef"));
+        assert!(matches!(err, GitApiError::BadObject(_)));
+
+        let err = GitApiError::from(failure(
+            "fatal: not a git repository (or any of the parent directories): .git",
+        ));
+        assert!(matches!(err, GitApiError::NotAGitRepository(_)));
+
+        let err = GitApiError::from(failure("fatal: the remote end hung up unexpectedly"));
+        assert!(matches!(err, GitApiError::CommandFailed(_)));
+        assert_eq!(err.status_code(), StatusCode::BAD_GATEWAY);
+        assert_eq!(
+            err.to_string(),
+            "Git command failed: `git log nope` terminated by exit code 128: \
+             fatal: the remote end hung up unexpectedly"
+        );
+    }
+
+    #[actix_web::test]
+    async fn test_error_response_envelope() {
+        let response = GitApiError::InvalidInput("Hash cannot be empty".into()).error_response();
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+
+        let body = to_bytes(response.into_body()).await.unwrap();
+        let json: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(json["error"]["code"], "invalid_input");
+        assert_eq!(
+            json["error"]["message"],
+            "Invalid input: Hash cannot be empty"
+        );
+    }
+}
@@ -0,0 +1,525 @@
+use crate::{application::GitApiError, utils::validation::validate_existing_repo_path};
+use actix_web::http::{header::HeaderValue, Method};
+use clap::{Parser, ValueEnum};
+use serde::Deserialize;
+use std::{
+    fmt, fs,
+    path::{Path, PathBuf},
+    str::FromStr,
+    time::Duration,
+};
+
+/// The config file used when none is given explicitly and it exists
+pub const DEFAULT_CONFIG_FILE: &str = "git-api.toml";
+
+#[derive(Parser, Debug, Default)]
+#[command(version, about = "A HTTP API for browsing git repositories")]
+/// Command line arguments, each of which can also be set
+/// through an environment variable.
+///
+/// Precedence (highest first): command line, environment,
+/// config file, built in defaults.
+pub struct Cli {
+    /// Path to a TOML config file
+    #[arg(short, long, env = "GIT_API_CONFIG")]
+    pub config: Option<PathBuf>,
+
+    /// Address to bind the http server to
+    #[arg(long, env = "GIT_API_BIND_ADDRESS")]
+    pub bind_address: Option<String>,
+
+    /// Port to bind the http server to
+    #[arg(long, env = "GIT_API_PORT")]
+    pub port: Option<u16>,
+
+    /// Log level (off, error, warn, info, debug, trace)
+    #[arg(long, env = "GIT_API_LOG_LEVEL")]
+    pub log_level: Option<String>,
+
+    /// Directory containing bare repositories, can be repeated
+    #[arg(long = "repo-root", env = "GIT_API_REPO_ROOTS", value_delimiter = ',')]
+    pub repo_roots: Vec<PathBuf>,
+
+    /// Origin allowed to make cross origin requests, can be repeated
+    #[arg(
+        long = "allowed-origin",
+        env = "GIT_API_ALLOWED_ORIGINS",
+        value_delimiter = ','
+    )]
+    pub allowed_origins: Vec<String>,
+
+    /// HTTP method allowed in cross origin requests, can be repeated
+    #[arg(
+        long = "allowed-method",
+        env = "GIT_API_ALLOWED_METHODS",
+        value_delimiter = ','
+    )]
+    pub allowed_methods: Vec<String>,
+
+    /// The maximum size of a request payload in bytes
+    #[arg(long, env = "GIT_API_MAX_PAYLOAD")]
+    pub max_payload: Option<usize>,
+
+    /// The memory budget of the object cache in bytes
+    #[arg(long, env = "GIT_API_OBJECT_CACHE_BYTES")]
+    pub object_cache_bytes: Option<usize>,
+
+    /// The memory budget of the tree cache in bytes
+    #[arg(long, env = "GIT_API_TREE_CACHE_BYTES")]
+    pub tree_cache_bytes: Option<usize>,
+
+    /// The memory budget of the last commit cache in bytes
+    #[arg(long, env = "GIT_API_LAST_COMMIT_CACHE_BYTES")]
+    pub last_commit_cache_bytes: Option<usize>,
+
+    /// How long a cached repository listing is trusted without checking its refs, in seconds
+    #[arg(long, env = "GIT_API_REPO_LIST_TTL_SECS")]
+    pub repo_list_ttl_secs: Option<u64>,
+
+    /// Whether to watch the repository roots for changes
+    #[arg(long, env = "GIT_API_WATCH_REPOS")]
+    pub watch_repos: Option<bool>,
+
+    /// How long a single git command may run before it's killed, in milliseconds
+    #[arg(long, env = "GIT_API_COMMAND_TIMEOUT_MS")]
+    pub command_timeout_ms: Option<u64>,
+
+    /// The maximum number of git processes running at once
+    #[arg(long, env = "GIT_API_MAX_CONCURRENT_COMMANDS")]
+    pub max_concurrent_commands: Option<usize>,
+
+    /// How long a whole request may take before it's aborted, in milliseconds
+    #[arg(long, env = "GIT_API_REQUEST_BUDGET_MS")]
+    pub request_budget_ms: Option<u64>,
+
+    /// How repositories are read
+    #[arg(long, env = "GIT_API_BACKEND")]
+    pub backend: Option<BackendKind>,
+}
+
+#[derive(Deserialize, Debug, Clone, Default)]
+#[serde(default, deny_unknown_fields)]
+/// The resolved configuration of the application
+pub struct Config {
+    pub server: ServerConfig,
+    pub repos: ReposConfig,
+    pub cors: CorsConfig,
+    pub limits: LimitsConfig,
+    pub cache: CacheConfig,
+    pub git: GitConfig,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+#[serde(default, deny_unknown_fields)]
+/// Configuration of the http server
+pub struct ServerConfig {
+    pub bind_address: String,
+    pub port: u16,
+    pub log_level: String,
+}
+impl Default for ServerConfig {
+    fn default() -> Self {
+        ServerConfig {
+            bind_address: "0.0.0.0".to_string(),
+            port: 6969,
+            log_level: "info".to_string(),
+        }
+    }
+}
+
+#[derive(Deserialize, Debug, Clone)]
+#[serde(default, deny_unknown_fields)]
+/// Configuration of where repositories live
+pub struct ReposConfig {
+    /// Directories holding the bare repositories, searched in order
+    pub roots: Vec<PathBuf>,
+}
+impl Default for ReposConfig {
+    fn default() -> Self {
+        ReposConfig {
+            roots: vec![PathBuf::from("/home/git/repos/public/")],
+        }
+    }
+}
+impl ReposConfig {
+    /// Try to find the path of a repository by name in the repo roots.
+    ///
+    /// The first root containing the repository wins.
+    pub fn resolve(&self, repo_name: &str) -> Result<PathBuf, GitApiError> {
+        for root in &self.roots {
+            match validate_existing_repo_path(root, repo_name) {
+                Err(GitApiError::RepoNotFound(_)) => continue,
+                result => return result,
+            }
+        }
+
+        Err(GitApiError::RepoNotFound(repo_name.to_string()))
+    }
+}
+
+#[derive(Deserialize, Debug, Clone)]
+#[serde(default, deny_unknown_fields)]
+/// Configuration of cross origin resource sharing
+pub struct CorsConfig {
+    /// Allowed origins, `*` allows any origin
+    pub allowed_origins: Vec<String>,
+    pub allowed_methods: Vec<String>,
+    /// How long (in seconds) browsers may cache preflight responses
+    pub max_age: usize,
+}
+impl Default for CorsConfig {
+    fn default() -> Self {
+        CorsConfig {
+            allowed_origins: vec!["https://antoniohickey.com".to_string()],
+            allowed_methods: vec!["GET".to_string(), "POST".to_string()],
+            max_age: 3600,
+        }
+    }
+}
+
+#[derive(Deserialize, Debug, Clone)]
+#[serde(default, deny_unknown_fields)]
+/// Configuration of request limits
+pub struct LimitsConfig {
+    /// The maximum size of a payload the application should accept
+    pub max_payload: usize,
+}
+impl Default for LimitsConfig {
+    fn default() -> Self {
+        LimitsConfig {
+            max_payload: 262_144,
+        }
+    }
+}
+
+#[derive(Deserialize, Debug, Clone)]
+#[serde(default, deny_unknown_fields)]
+/// Configuration of the in memory caches
+pub struct CacheConfig {
+    /// The memory budget of the object cache in bytes
+    pub object_cache_bytes: usize,
+    /// The memory budget of the tree cache in bytes
+    pub tree_cache_bytes: usize,
+    /// The memory budget of the last commit cache in bytes
+    pub last_commit_cache_bytes: usize,
+    /// How long a cached repository listing is trusted without checking its refs
+    pub repo_list_ttl_secs: u64,
+    /// Whether to watch the repository roots for changes
+    pub watch_repos: bool,
+}
+impl CacheConfig {
+    pub fn repo_list_ttl(&self) -> Duration {
+        Duration::from_secs(self.repo_list_ttl_secs)
+    }
+}
+impl Default for CacheConfig {
+    fn default() -> Self {
+        CacheConfig {
+            object_cache_bytes: 64 * 1024 * 1024,
+            tree_cache_bytes: 16 * 1024 * 1024,
+            last_commit_cache_bytes: 16 * 1024 * 1024,
+            repo_list_ttl_secs: 300,
+            watch_repos: true,
+        }
+    }
+}
+
+#[derive(Deserialize, Debug, Clone)]
+#[serde(default, deny_unknown_fields)]
+/// Configuration of how git processes are run
+pub struct GitConfig {
+    /// How long a single git command may run before it's killed
+    pub command_timeout_ms: u64,
+    /// The maximum number of git processes running at once
+    pub max_concurrent_commands: usize,
+    /// How long a whole request may take before it's aborted
+    pub request_budget_ms: u64,
+    /// How repositories are read
+    pub backend: BackendKind,
+}
+impl Default for GitConfig {
+    fn default() -> Self {
+        GitConfig {
+            command_timeout_ms: 10_000,
+            max_concurrent_commands: 32,
+            request_budget_ms: 30_000,
+            backend: BackendKind::Cli,
+        }
+    }
+}
+#[derive(Deserialize, ValueEnum, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "lowercase")]
+/// The implementations available to read repositories with
+pub enum BackendKind {
+    /// Shell out to the `git` executable
+    Cli,
+    /// Read repositories in-process with gitoxide
+    Gitoxide,
+}
+
+impl GitConfig {
+    pub fn command_timeout(&self) -> Duration {
+        Duration::from_millis(self.command_timeout_ms)
+    }
+
+    pub fn request_budget(&self) -> Duration {
+        Duration::from_millis(self.request_budget_ms)
+    }
+}
+
+impl Config {
+    /// Load the configuration by layering the config file,
+    /// environment variables and command line arguments on
+    /// top of the defaults, then validate the result.
+    pub fn load(cli: &Cli) -> Result<Config, ConfigError> {
+        let mut config = match &cli.config {
+            Some(path) => Self::from_file(path)?,
+            None if Path::new(DEFAULT_CONFIG_FILE).is_file() => {
+                Self::from_file(Path::new(DEFAULT_CONFIG_FILE))?
+            }
+            None => Config::default(),
+        };
+
+        config.apply_overrides(cli);
+        config.validate()?;
+
+        Ok(config)
+    }
+
+    /// Parse a config from a TOML file.
+    pub fn from_file(path: &Path) -> Result<Config, ConfigError> {
+        let contents = fs::read_to_string(path).map_err(|err| ConfigError::Io {
+            path: path.to_path_buf(),
+            err,
+        })?;
+
+        Self::from_toml(&contents).map_err(|err| ConfigError::Parse {
+            path: path.to_path_buf(),
+            err,
+        })
+    }
+
+    /// Parse a config from a TOML string.
+    pub fn from_toml(contents: &str) -> Result<Config, toml::de::Error> {
+        toml::from_str(contents)
+    }
+
+    /// Override values with the ones explicitly given through
+    /// the environment or the command line.
+    fn apply_overrides(&mut self, cli: &Cli) {
+        if let Some(bind_address) = &cli.bind_address {
+            self.server.bind_address = bind_address.clone();
+        }
+        if let Some(port) = cli.port {
+            self.server.port = port;
+        }
+        if let Some(log_level) = &cli.log_level {
+            self.server.log_level = log_level.clone();
+        }
+        if !cli.repo_roots.is_empty() {
+            self.repos.roots = cli.repo_roots.clone();
+        }
+        if !cli.allowed_origins.is_empty() {
+            self.cors.allowed_origins = cli.allowed_origins.clone();
+        }
+        if !cli.allowed_methods.is_empty() {
+            self.cors.allowed_methods = cli.allowed_methods.clone();
+        }
+        if let Some(max_payload) = cli.max_payload {
+            self.limits.max_payload = max_payload;
+        }
+        if let Some(object_cache_bytes) = cli.object_cache_bytes {
+            self.cache.object_cache_bytes = object_cache_bytes;
+        }
+        if let Some(tree_cache_bytes) = cli.tree_cache_bytes {
+            self.cache.tree_cache_bytes = tree_cache_bytes;
+        }
+        if let Some(last_commit_cache_bytes) = cli.last_commit_cache_bytes {
+            self.cache.last_commit_cache_bytes = last_commit_cache_bytes;
+        }
+        if let Some(repo_list_ttl_secs) = cli.repo_list_ttl_secs {
+            self.cache.repo_list_ttl_secs = repo_list_ttl_secs;
+        }
+        if let Some(watch_repos) = cli.watch_repos {
+            self.cache.watch_repos = watch_repos;
+        }
+        if let Some(command_timeout_ms) = cli.command_timeout_ms {
+            self.git.command_timeout_ms = command_timeout_ms;
+        }
+        if let Some(max_concurrent_commands) = cli.max_concurrent_commands {
+            self.git.max_concurrent_commands = max_concurrent_commands;
+        }
+        if let Some(request_budget_ms) = cli.request_budget_ms {
+            self.git.request_budget_ms = request_budget_ms;
+        }
+        if let Some(backend) = cli.backend {
+            self.git.backend = backend;
+        }
+    }
+
+    /// Make sure the configuration is usable before starting the server.
+    pub fn validate(&self) -> Result<(), ConfigError> {
+        if self.server.bind_address.trim().is_empty() {
+            return Err(ConfigError::Invalid(
+                "server.bind_address cannot be empty".to_string(),
+            ));
+        }
+
+        if log::LevelFilter::from_str(&self.server.log_level).is_err() {
+            return Err(ConfigError::Invalid(format!(
+                "server.log_level `{}` is not one of off, error, warn, info, debug, trace",
+                self.server.log_level
+            )));
+        }
+
+        if self.repos.roots.is_empty() {
+            return Err(ConfigError::Invalid(
+                "repos.roots needs at least one directory".to_string(),
+            ));
+        }
+        for root in &self.repos.roots {
+            if !root.is_dir() {
+                return Err(ConfigError::Invalid(format!(
+                    "repos.roots entry `{}` is not a directory",
+                    root.display()
+                )));
+            }
+        }
+
+        for origin in &self.cors.allowed_origins {
+            let is_url = origin.starts_with("http://") || origin.starts_with("https://");
+            if origin != "*" && (!is_url || HeaderValue::from_str(origin).is_err()) {
+                return Err(ConfigError::Invalid(format!(
+                    "cors.allowed_origins entry `{origin}` is not `*` or a http(s) origin"
+                )));
+            }
+        }
+
+        for method in &self.cors.allowed_methods {
+            if Method::from_str(&method.to_uppercase()).is_err() {
+                return Err(ConfigError::Invalid(format!(
+                    "cors.allowed_methods entry `{method}` is not a http method"
+                )));
+            }
+        }
+
+        if self.limits.max_payload == 0 {
+            return Err(ConfigError::Invalid(
+                "limits.max_payload must be greater than 0".to_string(),
+            ));
+        }
+
+        if self.git.command_timeout_ms == 0 || self.git.request_budget_ms == 0 {
+            return Err(ConfigError::Invalid(
+                "git.command_timeout_ms and git.request_budget_ms must be greater than 0"
+                    .to_string(),
+            ));
+        }
+        if self.git.max_concurrent_commands == 0 {
+            return Err(ConfigError::Invalid(
+                "git.max_concurrent_commands must be greater than 0".to_string(),
+            ));
+        }
+
+        Ok(())
+    }
+}
+
+#[derive(Debug)]
+/// Errors encountered while loading the configuration
+pub enum ConfigError {
+    /// The config file couldn't be read
+    Io { path: PathBuf, err: std::io::Error },
+
+    /// The config file isn't valid TOML or has unknown keys
+    Parse { path: PathBuf, err: toml::de::Error },
+
+    /// The configuration has an unusable value
+    Invalid(String),
+}
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ConfigError::Io { path, err } => {
+                write!(f, "failed to read config file {}: {err}", path.display())
+            }
+            ConfigError::Parse { path, err } => {
+                write!(f, "failed to parse config file {}: {err}", path.display())
+            }
+            ConfigError::Invalid(s) => write!(f, "{s}"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_partial_config() {
+        let config = Config::from_toml(
+            r#"
+            [server]
+            port = 8080
+
+            [repos]
+            roots = ["/srv/git/a", "/srv/git/b"]
+            "#,
+        )
+        .unwrap();
+
+        assert_eq!(config.server.port, 8080);
+        assert_eq!(config.server.bind_address, "0.0.0.0");
+        assert_eq!(config.repos.roots.len(), 2);
+        assert_eq!(config.limits.max_payload, 262_144);
+
+        assert!(Config::from_toml("[server]\nprot = 1").is_err());
+    }
+
+    #[test]
+    fn test_parse_example_config() {
+        assert!(Config::from_toml(include_str!("../git-api.example.toml")).is_ok());
+    }
+
+    #[test]
+    fn test_overrides_take_precedence() {
+        let mut config = Config::from_toml("[server]\nport = 8080\nlog_level = \"warn\"").unwrap();
+        config.apply_overrides(&Cli {
+            port: Some(9090),
+            allowed_origins: vec!["*".to_string()],
+            ..Cli::default()
+        });
+
+        assert_eq!(config.server.port, 9090);
+        assert_eq!(config.server.log_level, "warn");
+        assert_eq!(config.cors.allowed_origins, vec!["*"]);
+    }
+
+    #[test]
+    fn test_validate() {
+        let root = std::env::temp_dir();
+        let valid = Config {
+            repos: ReposConfig {
+                roots: vec![root.clone()],
+            },
+            ..Config::default()
+        };
+        assert!(valid.validate().is_ok());
+
+        let mut config = valid.clone();
+        config.repos.roots = vec![root.join("git-api-does-not-exist")];
+        assert!(config.validate().is_err());
+
+        let mut config = valid.clone();
+        config.server.log_level = "loud".to_string();
+        assert!(config.validate().is_err());
+
+        let mut config = valid.clone();
+        config.cors.allowed_origins = vec!["antoniohickey.com".to_string()];
+        assert!(config.validate().is_err());
+
+        let mut config = valid;
+        config.cors.allowed_methods = vec!["GET POST".to_string()];
+        assert!(config.validate().is_err());
+    }
+}
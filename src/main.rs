@@ -1,46 +1,80 @@
 mod application;
+mod config;
 mod repository;
 mod routes;
+#[cfg(test)]
+mod test_utils;
 mod utils;
 
 use actix_cors::Cors;
 use actix_web::{http, web, App, HttpServer};
 use application::AppState;
-use std::collections::HashMap;
-use std::sync::Arc;
-use tokio::sync::Mutex;
+use clap::Parser;
+use config::{Cli, Config};
 
 #[actix_web::main]
 async fn main() -> std::io::Result<()> {
-    // Leave debug stuff for now
-    std::env::set_var("RUST_LOG", "debug");
-    env_logger::init();
+    // Resolve the configuration from the config file,
+    // environment and command line arguments
+    let config = match Config::load(&Cli::parse()) {
+        Ok(config) => config,
+        Err(e) => {
+            eprintln!("Invalid configuration: {e}");
+            std::process::exit(1);
+        }
+    };
+
+    env_logger::Builder::new()
+        .parse_filters(&config.server.log_level)
+        .init();
+
+    let bind_address = (config.server.bind_address.clone(), config.server.port);
+    log::info!(
+        "Serving repositories from {:?} on {}:{}",
+        config.repos.roots,
+        bind_address.0,
+        bind_address.1
+    );
+
+    utils::commands::configure_git_limits(&config.git);
+    let request_budget = config.git.request_budget();
 
     // Initialize application's state
-    let app_state = web::Data::new(AppState {
-        max_payload: 262_144,
-        object_hash_cache: HashMap::new(),
-        repo_hash_cache: HashMap::new(),
-        repos_cache: Arc::new(Mutex::new(Vec::new())),
-    });
+    let app_state = web::Data::new(AppState::new(config));
 
     // Run the http server
     HttpServer::new(move || {
-        let cors_config = Cors::default()
-            .allowed_origin("https://antoniohickey.com")
-            .allowed_methods(vec!["GET", "POST"])
+        let cors = &app_state.config.cors;
+        let allowed_methods: Vec<String> = cors
+            .allowed_methods
+            .iter()
+            .map(|m| m.to_uppercase())
+            .collect();
+        let mut cors_config = Cors::default()
+            .allowed_methods(allowed_methods.iter().map(String::as_str))
             .allowed_headers(vec![
                 http::header::CONTENT_TYPE,
                 http::header::AUTHORIZATION,
             ])
-            .max_age(3600);
+            .expose_headers(vec![utils::request_id::REQUEST_ID_HEADER])
+            .max_age(cors.max_age);
+        for origin in &cors.allowed_origins {
+            cors_config = if origin == "*" {
+                cors_config.allow_any_origin()
+            } else {
+                cors_config.allowed_origin(origin)
+            };
+        }
 
         App::new()
             .app_data(app_state.clone())
+            .app_data(web::PayloadConfig::new(app_state.config.limits.max_payload))
+            .wrap_fn(move |req, srv| utils::deadline::enforce(request_budget, req, srv))
             .wrap(cors_config)
+            .wrap_fn(utils::request_id::assign)
             .configure(routes::config::configure_routes)
     })
-    .bind(("0.0.0.0", 6969))?
+    .bind(bind_address)?
     .run()
     .await
 }
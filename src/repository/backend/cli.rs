@@ -0,0 +1,313 @@
+use super::{GitBackend, GitRef, ObjectPath, TreeEntry};
+use crate::{
+    application::GitApiError,
+    utils::{
+        commands::{run_git_command, run_git_command_bytes, stream_git_command},
+        commits::Commit,
+    },
+};
+use async_trait::async_trait;
+use std::{
+    collections::{HashMap, HashSet},
+    path::Path,
+};
+
+/// The `git log` format every [`Commit`] is parsed from
+pub const LOG_FORMAT: &str = "--pretty=format:%x1e%H%x1f%an%x1f%ae%x1f%ad%x1f%P%x1f%B";
+
+/// A [`GitBackend`] shelling out to the `git` executable
+pub struct CliBackend;
+
+#[async_trait]
+impl GitBackend for CliBackend {
+    async fn resolve_ref(&self, repo_path: &Path, rev: &str) -> Result<String, GitApiError> {
+        // Peel to any object so full object ids are checked for existence
+        let rev = format!("{rev}^{{object}}");
+        let oid = run_git_command(repo_path, &["rev-parse", "--verify", &rev], false).await?;
+
+        Ok(oid.trim().to_string())
+    }
+
+    async fn read_tree(
+        &self,
+        repo_path: &Path,
+        treeish: &str,
+    ) -> Result<Vec<TreeEntry>, GitApiError> {
+        let output = run_git_command(repo_path, &["ls-tree", "-z", treeish], false).await?;
+
+        output
+            .split('\0')
+            .filter(|entry| !entry.is_empty())
+            .map(parse_tree_entry)
+            .collect()
+    }
+
+    async fn entry_at(
+        &self,
+        repo_path: &Path,
+        revision: &str,
+        path: &str,
+    ) -> Result<Option<TreeEntry>, GitApiError> {
+        let path = path.trim_matches('/');
+        if path.is_empty() {
+            let tree = format!("{revision}^{{tree}}");
+            let oid = run_git_command(repo_path, &["rev-parse", "--verify", &tree], false).await?;
+
+            return Ok(Some(TreeEntry {
+                mode: "040000".to_string(),
+                kind: "tree".to_string(),
+                oid: oid.trim().to_string(),
+                name: String::new(),
+            }));
+        }
+
+        // Lists nothing when there's nothing at the path, the path being
+        // matched as is rather than as a pattern
+        let args = [
+            "--literal-pathspecs",
+            "ls-tree",
+            "-z",
+            "--full-tree",
+            revision,
+            "--",
+            path,
+        ];
+        let output = run_git_command(repo_path, &args, false).await?;
+
+        let Some(entry) = output.split('\0').find(|entry| !entry.is_empty()) else {
+            return Ok(None);
+        };
+        let mut entry = parse_tree_entry(entry)?;
+        // Named by its whole path
+        if let Some((_, name)) = entry.name.rsplit_once('/') {
+            entry.name = name.to_string();
+        }
+
+        Ok(Some(entry))
+    }
+
+    async fn read_blob(&self, repo_path: &Path, oid: &str) -> Result<Vec<u8>, GitApiError> {
+        run_git_command_bytes(repo_path, &["cat-file", "blob", oid]).await
+    }
+
+    async fn log(
+        &self,
+        repo_path: &Path,
+        rev: &str,
+        path: Option<&str>,
+        limit: Option<usize>,
+    ) -> Result<Vec<Commit>, GitApiError> {
+        let limit = limit.map(|limit| format!("--max-count={limit}"));
+
+        // Paths are matched as is rather than as patterns
+        let mut args = vec![
+            "--literal-pathspecs",
+            "log",
+            "--no-merges",
+            "--date=iso-strict",
+            LOG_FORMAT,
+        ];
+        args.extend(limit.as_deref());
+        args.push(rev);
+        if let Some(path) = path {
+            args.extend(["--", path]);
+        }
+
+        let log_output = run_git_command(repo_path, &args, false).await?;
+
+        Ok(log_output
+            .split('\x1e')
+            .filter(|part| !part.trim().is_empty())
+            .map(Commit::from)
+            .collect())
+    }
+
+    async fn last_commits(
+        &self,
+        repo_path: &Path,
+        rev: &str,
+        directory: Option<&str>,
+        names: &[String],
+    ) -> Result<HashMap<String, Commit>, GitApiError> {
+        // Messages can have blank lines, so the paths follow a marker
+        let format = format!("{LOG_FORMAT}%x1d");
+        // Paths are matched as is rather than as patterns
+        let mut args = vec![
+            "--literal-pathspecs",
+            "log",
+            "--no-merges",
+            "--no-renames",
+            "--date=iso-strict",
+            &format,
+            "-z",
+            "--name-only",
+            rev,
+        ];
+        if let Some(directory) = directory {
+            args.extend(["--", directory]);
+        }
+
+        let prefix = directory.map(|d| format!("{d}/")).unwrap_or_default();
+        let names: HashSet<&str> = names.iter().map(String::as_str).collect();
+        let mut last_commits = HashMap::new();
+        if names.is_empty() {
+            return Ok(last_commits);
+        }
+
+        // Git is stopped as soon as every entry has its last commit
+        stream_git_command(repo_path, &args, b'\x1e', |record| {
+            let Some((header, paths)) = record.rsplit_once('\x1d') else {
+                return true;
+            };
+
+            // Every path is listed as "<directory>/<name>[/...]\0"
+            let mut commit = None;
+            for path in paths.trim_start_matches('\n').split('\0') {
+                let Some(name) = path.strip_prefix(&prefix).and_then(|p| p.split('/').next())
+                else {
+                    continue;
+                };
+                if names.contains(name) && !last_commits.contains_key(name) {
+                    let commit = commit.get_or_insert_with(|| Commit::from(header));
+                    last_commits.insert(name.to_string(), commit.clone());
+                }
+            }
+
+            last_commits.len() < names.len()
+        })
+        .await?;
+
+        Ok(last_commits)
+    }
+
+    async fn root_commit(
+        &self,
+        repo_path: &Path,
+        rev: &str,
+    ) -> Result<Option<Commit>, GitApiError> {
+        let args = [
+            "log",
+            "--max-parents=0",
+            "--date=iso-strict",
+            LOG_FORMAT,
+            rev,
+        ];
+        let log_output = run_git_command(repo_path, &args, false).await?;
+
+        // Histories can have several roots, the oldest is listed last
+        Ok(log_output
+            .split('\x1e')
+            .filter(|part| !part.trim().is_empty())
+            .map(Commit::from)
+            .next_back())
+    }
+
+    async fn default_branch(&self, repo_path: &Path) -> Result<Option<String>, GitApiError> {
+        let args = ["symbolic-ref", "--quiet", "--short", "HEAD"];
+
+        // `--quiet` makes a detached HEAD exit with 1 and no message
+        match run_git_command(repo_path, &args, false).await {
+            Ok(branch) => Ok(Some(branch.trim().to_string())),
+            Err(GitApiError::CommandFailed(failure)) if failure.status == Some(1) => Ok(None),
+            Err(e) => Err(e),
+        }
+    }
+
+    async fn refs(&self, repo_path: &Path) -> Result<Vec<GitRef>, GitApiError> {
+        let output = run_git_command(
+            repo_path,
+            &["for-each-ref", "--format=%(objectname) %(refname)"],
+            false,
+        )
+        .await?;
+
+        Ok(output
+            .lines()
+            .filter_map(|line| line.split_once(' '))
+            .map(|(target, name)| GitRef {
+                name: name.to_string(),
+                target: target.to_string(),
+            })
+            .collect())
+    }
+
+    async fn object_paths(&self, repo_path: &Path) -> Result<Vec<ObjectPath>, GitApiError> {
+        // Every path an object lives at was introduced by some commit, so
+        // diffing every commit against its (first) parent finds them all.
+        let output = run_git_command(
+            repo_path,
+            &[
+                "log",
+                "--all",
+                "--root",
+                "-t",
+                "--raw",
+                "--no-abbrev",
+                "--no-renames",
+                "--diff-merges=first-parent",
+                "-z",
+                "--format=%x1e%H %T",
+            ],
+            false,
+        )
+        .await?;
+
+        let mut seen = HashSet::new();
+        let mut objects = Vec::new();
+        for record in output.split('\x1e').filter(|record| !record.is_empty()) {
+            // A record is "<commit> <root tree>\0" followed by
+            // ":<old mode> <new mode> <old oid> <new oid> <status>\0<path>\0"
+            // for every changed entry
+            let mut fields = record.split('\0');
+            let (commit, tree) = fields
+                .next()
+                .and_then(|header| header.split_once(' '))
+                .ok_or_else(|| GitApiError::Backend(format!("Bad log record {record}")))?;
+
+            objects.push(ObjectPath {
+                oid: commit.to_string(),
+                path: None,
+            });
+            let mut found = vec![(tree, "")];
+
+            while let (Some(change), Some(path)) = (fields.next(), fields.next()) {
+                let change: Vec<&str> = change.trim_start_matches(['\n', ':']).split(' ').collect();
+                match change.as_slice() {
+                    [_, "160000", ..] => {} // Submodules aren't objects of this repository
+                    [_, _, _, oid, _] if oid.bytes().any(|b| b != b'0') => found.push((oid, path)),
+                    _ => {}
+                }
+            }
+
+            for (oid, path) in found {
+                if seen.insert((oid, path)) {
+                    objects.push(ObjectPath {
+                        oid: oid.to_string(),
+                        path: Some(path.to_string()),
+                    });
+                }
+            }
+        }
+
+        Ok(objects)
+    }
+}
+
+/// Parse an entry of `git ls-tree -z` output, which looks like
+/// `<mode> <kind> <oid>\t<name>`.
+fn parse_tree_entry(entry: &str) -> Result<TreeEntry, GitApiError> {
+    let (info, name) = entry
+        .split_once('\t')
+        .ok_or_else(|| GitApiError::Backend(format!("Bad tree entry {entry}")))?;
+    let mut info = info.split(' ');
+
+    match (info.next(), info.next(), info.next()) {
+        (Some(mode), Some(kind), Some(oid)) => Ok(TreeEntry {
+            mode: mode.to_string(),
+            kind: kind.to_string(),
+            oid: oid.to_string(),
+            name: name.to_string(),
+        }),
+        _ => Err(GitApiError::Backend(format!("Bad tree entry {entry}"))),
+    }
+}
@@ -0,0 +1,223 @@
+//! An in memory [`GitBackend`] for unit testing the models without
+//! creating repositories on disk.
+
+use super::{GitBackend, GitRef, ObjectPath, TreeEntry};
+use crate::{application::GitApiError, utils::commits::Commit};
+use async_trait::async_trait;
+use std::{collections::HashMap, path::Path};
+
+/// A [`GitBackend`] serving a single, hand built repository.
+///
+/// Refs and commits are looked up verbatim and objects with a path by
+/// prefix. Commits peel to their tree where a tree is expected (like
+/// `rev^{tree}`), and logs ignore the revision, only filtering by path.
+#[derive(Default)]
+pub struct FakeBackend {
+    pub trees: HashMap<String, Vec<TreeEntry>>,
+    /// The tree of every commit by the commit's id
+    pub commit_trees: HashMap<String, String>,
+    pub blobs: HashMap<String, Vec<u8>>,
+    /// Commits, newest first, along with the paths they touched
+    pub commits: Vec<(Commit, Vec<String>)>,
+    pub refs: Vec<GitRef>,
+    /// The branch `HEAD` points at
+    pub head: Option<String>,
+    pub object_paths: Vec<ObjectPath>,
+}
+impl FakeBackend {
+    /// Add a tree reachable by `treeish`.
+    pub fn tree(mut self, treeish: &str, entries: &[(&str, &str, &str)]) -> Self {
+        let entries = entries
+            .iter()
+            .map(|(kind, oid, name)| TreeEntry {
+                mode: if *kind == "tree" { "040000" } else { "100644" }.to_string(),
+                kind: kind.to_string(),
+                oid: oid.to_string(),
+                name: name.to_string(),
+            })
+            .collect();
+        self.trees.insert(treeish.to_string(), entries);
+        self
+    }
+
+    /// Record the tree of a commit.
+    pub fn tree_of(mut self, commit: &str, tree: &str) -> Self {
+        self.commit_trees
+            .insert(commit.to_string(), tree.to_string());
+        self
+    }
+
+    /// Peel a commit to its tree, anything else being left as is.
+    fn peel<'a>(&'a self, treeish: &'a str) -> &'a str {
+        self.commit_trees
+            .get(treeish)
+            .map_or(treeish, String::as_str)
+    }
+
+    /// Add a blob living at `path`.
+    pub fn blob(mut self, oid: &str, path: &str, content: &[u8]) -> Self {
+        self.blobs.insert(oid.to_string(), content.to_vec());
+        self.path(oid, path)
+    }
+
+    /// Record the path an object lives at.
+    pub fn path(mut self, oid: &str, path: &str) -> Self {
+        self.object_paths.push(ObjectPath {
+            oid: oid.to_string(),
+            path: Some(path.to_string()),
+        });
+        self
+    }
+
+    /// Add a commit older than every commit added before it.
+    pub fn commit(mut self, hash: &str, message: &str, paths: &[&str]) -> Self {
+        let commit = Commit::new(
+            hash,
+            "Test Author",
+            "author@example.com",
+            "2024-01-02T03:04:05+00:00",
+            message,
+        );
+        self.commits
+            .push((commit, paths.iter().map(|p| p.to_string()).collect()));
+        self
+    }
+}
+
+#[async_trait]
+impl GitBackend for FakeBackend {
+    async fn resolve_ref(&self, repo_path: &Path, rev: &str) -> Result<String, GitApiError> {
+        if let Some(treeish) = rev.strip_suffix("^{tree}") {
+            let treeish = self.resolve_ref(repo_path, treeish).await?;
+            let tree = self.peel(&treeish);
+            if !self.trees.contains_key(tree) {
+                return Err(GitApiError::ObjectNotFound(rev.to_string()));
+            }
+            return Ok(tree.to_string());
+        }
+
+        self.refs
+            .iter()
+            .find(|r| r.name == rev || r.name == format!("refs/heads/{rev}"))
+            .map(|r| r.target.clone())
+            .or_else(|| self.commit_trees.contains_key(rev).then(|| rev.to_string()))
+            .or_else(|| {
+                self.object_paths
+                    .iter()
+                    .find(|object| object.oid.starts_with(rev))
+                    .map(|object| object.oid.clone())
+            })
+            .ok_or(GitApiError::ObjectNotFound(rev.to_string()))
+    }
+
+    async fn read_tree(
+        &self,
+        _repo_path: &Path,
+        treeish: &str,
+    ) -> Result<Vec<TreeEntry>, GitApiError> {
+        self.trees
+            .get(self.peel(treeish))
+            .cloned()
+            .ok_or(GitApiError::ObjectNotFound(treeish.to_string()))
+    }
+
+    async fn entry_at(
+        &self,
+        _repo_path: &Path,
+        revision: &str,
+        path: &str,
+    ) -> Result<Option<TreeEntry>, GitApiError> {
+        let mut entry = TreeEntry {
+            mode: "040000".to_string(),
+            kind: "tree".to_string(),
+            oid: self.peel(revision).to_string(),
+            name: String::new(),
+        };
+        for name in path.split('/').filter(|name| !name.is_empty()) {
+            let found = self
+                .trees
+                .get(&entry.oid)
+                .and_then(|entries| entries.iter().find(|e| e.name == name));
+            match found {
+                Some(found) => entry = found.clone(),
+                None => return Ok(None),
+            }
+        }
+
+        Ok(Some(entry))
+    }
+
+    async fn read_blob(&self, _repo_path: &Path, oid: &str) -> Result<Vec<u8>, GitApiError> {
+        self.blobs
+            .get(oid)
+            .cloned()
+            .ok_or(GitApiError::ObjectNotFound(oid.to_string()))
+    }
+
+    async fn log(
+        &self,
+        _repo_path: &Path,
+        _rev: &str,
+        path: Option<&str>,
+        limit: Option<usize>,
+    ) -> Result<Vec<Commit>, GitApiError> {
+        Ok(self
+            .commits
+            .iter()
+            .filter(|(_, paths)| {
+                path.is_none_or(|path| {
+                    paths
+                        .iter()
+                        .any(|p| p == path || p.starts_with(&format!("{path}/")))
+                })
+            })
+            .map(|(commit, _)| commit.clone())
+            .take(limit.unwrap_or(usize::MAX))
+            .collect())
+    }
+
+    async fn last_commits(
+        &self,
+        _repo_path: &Path,
+        _rev: &str,
+        directory: Option<&str>,
+        names: &[String],
+    ) -> Result<HashMap<String, Commit>, GitApiError> {
+        let prefix = directory.map(|d| format!("{d}/")).unwrap_or_default();
+
+        let mut last_commits = HashMap::new();
+        for (commit, paths) in &self.commits {
+            for path in paths {
+                let Some(name) = path.strip_prefix(&prefix).and_then(|p| p.split('/').next())
+                else {
+                    continue;
+                };
+                if names.iter().any(|n| n == name) && !last_commits.contains_key(name) {
+                    last_commits.insert(name.to_string(), commit.clone());
+                }
+            }
+        }
+
+        Ok(last_commits)
+    }
+
+    async fn root_commit(
+        &self,
+        _repo_path: &Path,
+        _rev: &str,
+    ) -> Result<Option<Commit>, GitApiError> {
+        Ok(self.commits.last().map(|(commit, _)| commit.clone()))
+    }
+
+    async fn default_branch(&self, _repo_path: &Path) -> Result<Option<String>, GitApiError> {
+        Ok(self.head.clone())
+    }
+
+    async fn refs(&self, _repo_path: &Path) -> Result<Vec<GitRef>, GitApiError> {
+        Ok(self.refs.clone())
+    }
+
+    async fn object_paths(&self, _repo_path: &Path) -> Result<Vec<ObjectPath>, GitApiError> {
+        Ok(self.object_paths.clone())
+    }
+}
@@ -0,0 +1,644 @@
+use super::{GitBackend, GitRef, ObjectPath, TreeEntry};
+use crate::{
+    application::GitApiError,
+    utils::{commands::acquire_git_slot, commits::Commit},
+};
+use async_trait::async_trait;
+use chrono::{DateTime, FixedOffset};
+use gix::{
+    object::tree::EntryKind, revision::walk::Sorting, traverse::commit::simple::CommitTimeOrder,
+    ObjectId, Repository,
+};
+use std::{
+    cmp::Reverse,
+    collections::{BinaryHeap, HashMap, HashSet},
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    time::Instant,
+};
+use tokio::time::timeout;
+
+/// A [`GitBackend`] reading repositories in-process using gitoxide,
+/// saving a fork and exec of `git` for every operation.
+pub struct GitoxideBackend;
+
+impl GitoxideBackend {
+    /// Run blocking repository work on the blocking thread pool so
+    /// it never stalls the async workers, within the same limits as git
+    /// processes: it takes one of their slots until the work is over and
+    /// is told to stop once out of time or no longer awaited.
+    async fn with_repo<T, F>(repo_path: &Path, f: F) -> Result<T, GitApiError>
+    where
+        T: Send + 'static,
+        F: FnOnce(&Repository, &Interrupt) -> Result<T, GitApiError> + Send + 'static,
+    {
+        let slot = acquire_git_slot().await?;
+        let time_limit = slot.time_limit;
+        let interrupt = Interrupt {
+            stopped: Arc::new(AtomicBool::new(false)),
+            deadline: Instant::now() + time_limit,
+            repo_path: repo_path.to_path_buf(),
+        };
+        let _stop_on_drop = StopOnDrop(interrupt.stopped.clone());
+
+        let task = tokio::task::spawn_blocking(move || {
+            // The slot is only given back once the work really is over
+            let _slot = slot;
+            let repo = gix::open(&interrupt.repo_path)
+                .map_err(|e| backend_error(&interrupt.repo_path, e))?;
+            f(&repo, &interrupt)
+        });
+
+        match timeout(time_limit, task).await {
+            Ok(result) => {
+                result.map_err(|e| GitApiError::Backend(format!("Gitoxide task failed: {e}")))?
+            }
+            Err(_) => Err(timed_out(repo_path)),
+        }
+    }
+}
+
+/// Tells blocking repository work to stop, checked between the steps of
+/// anything walking history.
+struct Interrupt {
+    stopped: Arc<AtomicBool>,
+    deadline: Instant,
+    repo_path: PathBuf,
+}
+impl Interrupt {
+    /// Fail if the work should stop.
+    fn check(&self) -> Result<(), GitApiError> {
+        if self.stopped.load(Ordering::Relaxed) || Instant::now() >= self.deadline {
+            return Err(timed_out(&self.repo_path));
+        }
+        Ok(())
+    }
+}
+
+/// Stops the work of an [`Interrupt`] once its future is dropped.
+struct StopOnDrop(Arc<AtomicBool>);
+impl Drop for StopOnDrop {
+    fn drop(&mut self) {
+        self.0.store(true, Ordering::Relaxed);
+    }
+}
+
+/// Remembers what lives at a path in the last commit it was looked up
+/// in, as walking history newest first looks up every commit twice: as
+/// itself and as the parent of the commit before.
+struct PathLookup {
+    path: String,
+    last: Option<(ObjectId, Option<ObjectId>)>,
+}
+impl PathLookup {
+    fn new(path: &str) -> PathLookup {
+        PathLookup {
+            path: path.to_string(),
+            last: None,
+        }
+    }
+
+    /// Find the object id of what lives at the path in a commit.
+    fn at(&mut self, repo: &Repository, commit: ObjectId) -> Result<Option<ObjectId>, GitApiError> {
+        if let Some((last_commit, entry)) = self.last {
+            if last_commit == commit {
+                return Ok(entry);
+            }
+        }
+
+        let entry = entry_at_path(repo, commit, &self.path)?;
+        self.last = Some((commit, entry));
+        Ok(entry)
+    }
+
+    /// Find the object ids of what lives at the path in a commit and in
+    /// its first parent, if any.
+    fn at_and_before(
+        &mut self,
+        repo: &Repository,
+        commit: ObjectId,
+        parent: Option<ObjectId>,
+    ) -> Result<(Option<ObjectId>, Option<ObjectId>), GitApiError> {
+        // The parent is looked up last so it's remembered for the next
+        // commit walked
+        let current = self.at(repo, commit)?;
+        let previous = match parent {
+            Some(parent) => self.at(repo, parent)?,
+            None => None,
+        };
+        Ok((current, previous))
+    }
+}
+
+/// Commits waiting to be walked, handed out the newest first (the ones
+/// queued first among equally old ones) like `git log` does, each commit
+/// being queued once.
+#[derive(Default)]
+struct CommitQueue {
+    queue: BinaryHeap<(i64, Reverse<usize>, ObjectId)>,
+    seen: HashSet<ObjectId>,
+}
+impl CommitQueue {
+    fn push(&mut self, repo: &Repository, id: ObjectId) -> Result<(), GitApiError> {
+        if !self.seen.insert(id) {
+            return Ok(());
+        }
+
+        let commit = repo.find_commit(id).map_err(|e| backend_error(id, e))?;
+        let time = commit.time().map_err(|e| backend_error(id, e))?;
+        self.queue
+            .push((time.seconds, Reverse(self.seen.len()), id));
+        Ok(())
+    }
+
+    fn pop(&mut self) -> Option<ObjectId> {
+        self.queue.pop().map(|(_, _, id)| id)
+    }
+}
+
+#[async_trait]
+impl GitBackend for GitoxideBackend {
+    async fn resolve_ref(&self, repo_path: &Path, rev: &str) -> Result<String, GitApiError> {
+        let rev = rev.to_string();
+
+        Self::with_repo(repo_path, move |repo, _| {
+            Ok(resolve(repo, &rev)?.to_string())
+        })
+        .await
+    }
+
+    async fn read_tree(
+        &self,
+        repo_path: &Path,
+        treeish: &str,
+    ) -> Result<Vec<TreeEntry>, GitApiError> {
+        let treeish = treeish.to_string();
+
+        Self::with_repo(repo_path, move |repo, _| {
+            let tree = repo
+                .find_object(resolve(repo, &treeish)?)
+                .map_err(|_| GitApiError::ObjectNotFound(treeish.clone()))?
+                .peel_to_tree()
+                .map_err(|_| GitApiError::InvalidInput(format!("{treeish} is not a tree")))?;
+
+            tree.iter()
+                .map(|entry| {
+                    let entry = entry.map_err(|e| backend_error(&treeish, e))?;
+
+                    Ok(TreeEntry {
+                        mode: format!("{:06o}", entry.mode().value()),
+                        kind: entry_kind_name(entry.mode().kind()).to_string(),
+                        oid: entry.oid().to_string(),
+                        name: entry.filename().to_string(),
+                    })
+                })
+                .collect()
+        })
+        .await
+    }
+
+    async fn entry_at(
+        &self,
+        repo_path: &Path,
+        revision: &str,
+        path: &str,
+    ) -> Result<Option<TreeEntry>, GitApiError> {
+        let revision = revision.to_string();
+        let path = path.trim_matches('/').to_string();
+
+        Self::with_repo(repo_path, move |repo, _| {
+            let tree = repo
+                .find_object(resolve(repo, &revision)?)
+                .map_err(|_| GitApiError::ObjectNotFound(revision.clone()))?
+                .peel_to_tree()
+                .map_err(|_| GitApiError::InvalidInput(format!("{revision} is not a tree")))?;
+            if path.is_empty() {
+                return Ok(Some(TreeEntry {
+                    mode: "040000".to_string(),
+                    kind: "tree".to_string(),
+                    oid: tree.id.to_string(),
+                    name: String::new(),
+                }));
+            }
+
+            let entry = tree
+                .lookup_entry_by_path(&path)
+                .map_err(|e| backend_error(&path, e))?;
+
+            Ok(entry.map(|entry| TreeEntry {
+                mode: format!("{:06o}", entry.mode().value()),
+                kind: entry_kind_name(entry.mode().kind()).to_string(),
+                oid: entry.object_id().to_string(),
+                name: entry.filename().to_string(),
+            }))
+        })
+        .await
+    }
+
+    async fn read_blob(&self, repo_path: &Path, oid: &str) -> Result<Vec<u8>, GitApiError> {
+        let oid = oid.to_string();
+
+        Self::with_repo(repo_path, move |repo, _| {
+            let blob = repo
+                .find_object(resolve(repo, &oid)?)
+                .map_err(|_| GitApiError::ObjectNotFound(oid.clone()))?
+                .try_into_blob()
+                .map_err(|_| GitApiError::InvalidInput(format!("{oid} is not a blob")))?;
+
+            Ok(blob.data.clone())
+        })
+        .await
+    }
+
+    async fn log(
+        &self,
+        repo_path: &Path,
+        rev: &str,
+        path: Option<&str>,
+        limit: Option<usize>,
+    ) -> Result<Vec<Commit>, GitApiError> {
+        let rev = rev.to_string();
+        let path = path.map(|path| path.trim_matches('/').to_string());
+
+        Self::with_repo(repo_path, move |repo, interrupt| {
+            let tip = resolve(repo, &rev)?;
+            let walk = repo
+                .rev_walk([tip])
+                .sorting(Sorting::ByCommitTime(CommitTimeOrder::NewestFirst))
+                .all()
+                .map_err(|e| backend_error(&rev, e))?;
+
+            let mut lookup = path.as_deref().map(PathLookup::new);
+            let mut commits = Vec::new();
+            for info in walk {
+                if limit.is_some_and(|limit| commits.len() >= limit) {
+                    break;
+                }
+                interrupt.check()?;
+
+                let info = info.map_err(|e| backend_error(&rev, e))?;
+                let parents: Vec<ObjectId> = info.parent_ids().map(|id| id.detach()).collect();
+
+                // Like `git log --no-merges`
+                if parents.len() > 1 {
+                    continue;
+                }
+
+                // Only keep commits that changed what lives at the path
+                if let Some(lookup) = &mut lookup {
+                    let (current, previous) =
+                        lookup.at_and_before(repo, info.id, parents.first().copied())?;
+                    if current == previous {
+                        continue;
+                    }
+                }
+
+                let commit = info.object().map_err(|e| backend_error(&rev, e))?;
+                commits.push(to_commit(&commit)?);
+            }
+
+            Ok(commits)
+        })
+        .await
+    }
+
+    async fn last_commits(
+        &self,
+        repo_path: &Path,
+        rev: &str,
+        directory: Option<&str>,
+        names: &[String],
+    ) -> Result<HashMap<String, Commit>, GitApiError> {
+        let rev = rev.to_string();
+        let directory = directory.unwrap_or_default().trim_matches('/').to_string();
+        let mut pending = names.to_vec();
+
+        Self::with_repo(repo_path, move |repo, interrupt| {
+            // Like `git log -- <directory>`, merges that kept the directory
+            // of a parent as is only lead on to that parent, so changes the
+            // merge left out are never found
+            let simplify = !directory.is_empty();
+            let mut queue = CommitQueue::default();
+            queue.push(repo, resolve(repo, &rev)?)?;
+
+            let mut lookup = PathLookup::new(&directory);
+            let mut last_commits = HashMap::new();
+            while let Some(id) = queue.pop() {
+                if pending.is_empty() {
+                    break;
+                }
+                interrupt.check()?;
+
+                let commit = repo.find_commit(id).map_err(|e| backend_error(&rev, e))?;
+                let parents: Vec<ObjectId> = commit.parent_ids().map(|id| id.detach()).collect();
+
+                // Like `git log --no-merges`, merges are only walked through
+                if parents.len() > 1 {
+                    let current = lookup.at(repo, id)?;
+                    let mut same = None;
+                    if simplify {
+                        for &parent in &parents {
+                            if lookup.at(repo, parent)? == current {
+                                same = Some(parent);
+                                break;
+                            }
+                        }
+                    }
+                    for parent in same.map_or(parents, |parent| vec![parent]) {
+                        queue.push(repo, parent)?;
+                    }
+                    continue;
+                }
+                if let Some(&parent) = parents.first() {
+                    queue.push(repo, parent)?;
+                }
+
+                // Nothing in the directory changed if its tree didn't
+                let (current, previous) =
+                    lookup.at_and_before(repo, id, parents.first().copied())?;
+                if current == previous {
+                    continue;
+                }
+
+                // Entries are done once a commit changed what lives at them
+                let current = tree_entries(repo, current)?;
+                let previous = tree_entries(repo, previous)?;
+                let mut changed = Vec::new();
+                for (i, name) in pending.iter().enumerate() {
+                    if current.get(name) != previous.get(name) {
+                        changed.push(i);
+                    }
+                }
+                if changed.is_empty() {
+                    continue;
+                }
+
+                let commit = to_commit(&commit)?;
+                for i in changed.into_iter().rev() {
+                    let name = pending.swap_remove(i);
+                    last_commits.insert(name, commit.clone());
+                }
+            }
+
+            Ok(last_commits)
+        })
+        .await
+    }
+
+    async fn root_commit(
+        &self,
+        repo_path: &Path,
+        rev: &str,
+    ) -> Result<Option<Commit>, GitApiError> {
+        let rev = rev.to_string();
+
+        Self::with_repo(repo_path, move |repo, interrupt| {
+            let walk = repo
+                .rev_walk([resolve(repo, &rev)?])
+                .sorting(Sorting::ByCommitTime(CommitTimeOrder::NewestFirst))
+                .all()
+                .map_err(|e| backend_error(&rev, e))?;
+
+            // The oldest commit without parents is the last one walked,
+            // only read once the walk is over
+            let mut root = None;
+            for info in walk {
+                interrupt.check()?;
+                let info = info.map_err(|e| backend_error(&rev, e))?;
+                if info.parent_ids().next().is_none() {
+                    root = Some(info.id);
+                }
+            }
+
+            root.map(|id| {
+                let commit = repo.find_commit(id).map_err(|e| backend_error(&rev, e))?;
+                to_commit(&commit)
+            })
+            .transpose()
+        })
+        .await
+    }
+
+    async fn default_branch(&self, repo_path: &Path) -> Result<Option<String>, GitApiError> {
+        Self::with_repo(repo_path, move |repo, _| {
+            let head = repo.head_name().map_err(|e| backend_error("HEAD", e))?;
+
+            Ok(head.map(|name| name.shorten().to_string()))
+        })
+        .await
+    }
+
+    async fn refs(&self, repo_path: &Path) -> Result<Vec<GitRef>, GitApiError> {
+        Self::with_repo(repo_path, move |repo, _| {
+            let platform = repo
+                .references()
+                .map_err(|e| backend_error("references", e))?;
+            let mut refs = Vec::new();
+
+            for reference in platform.all().map_err(|e| backend_error("references", e))? {
+                let mut reference = reference.map_err(|e| backend_error("references", e))?;
+                let name = reference.name().as_bstr().to_string();
+                let target = match reference.try_id() {
+                    Some(id) => id.detach(),
+                    None => reference
+                        .peel_to_id()
+                        .map_err(|e| backend_error(&name, e))?
+                        .detach(),
+                };
+
+                refs.push(GitRef {
+                    name,
+                    target: target.to_string(),
+                });
+            }
+
+            refs.sort_by(|a, b| a.name.cmp(&b.name));
+            Ok(refs)
+        })
+        .await
+    }
+
+    async fn object_paths(&self, repo_path: &Path) -> Result<Vec<ObjectPath>, GitApiError> {
+        let tips: Vec<ObjectId> = self
+            .refs(repo_path)
+            .await?
+            .into_iter()
+            .filter_map(|r| ObjectId::from_hex(r.target.as_bytes()).ok())
+            .collect();
+
+        Self::with_repo(repo_path, move |repo, interrupt| {
+            let mut seen = HashSet::new();
+            let mut objects = Vec::new();
+
+            // Peel tags so every tip is a commit
+            let commit_tips: Vec<ObjectId> = tips
+                .into_iter()
+                .filter_map(|tip| repo.find_object(tip).ok()?.peel_to_commit().ok())
+                .map(|commit| commit.id)
+                .collect();
+
+            let walk = repo
+                .rev_walk(commit_tips)
+                .all()
+                .map_err(|e| backend_error("rev-list", e))?;
+
+            // Record every commit, then every object in its tree at every
+            // path it lives at, only descending into a tree the first time
+            // it's seen at a given path
+            let mut trees = Vec::new();
+            for info in walk {
+                interrupt.check()?;
+                let info = info.map_err(|e| backend_error("rev-list", e))?;
+                objects.push(ObjectPath {
+                    oid: info.id.to_string(),
+                    path: None,
+                });
+
+                let commit = info.object().map_err(|e| backend_error("rev-list", e))?;
+                let tree_id = commit.tree_id().map_err(|e| backend_error("rev-list", e))?;
+                trees.push((tree_id.detach(), String::new()));
+            }
+
+            while let Some((tree_id, path)) = trees.pop() {
+                interrupt.check()?;
+                if !seen.insert((tree_id, path.clone())) {
+                    continue;
+                }
+
+                let tree = repo
+                    .find_tree(tree_id)
+                    .map_err(|e| backend_error("rev-list", e))?;
+                for entry in tree.iter() {
+                    let entry = entry.map_err(|e| backend_error("rev-list", e))?;
+                    let entry_path = if path.is_empty() {
+                        entry.filename().to_string()
+                    } else {
+                        format!("{path}/{}", entry.filename())
+                    };
+
+                    match entry.mode().kind() {
+                        EntryKind::Tree => trees.push((entry.object_id(), entry_path)),
+                        EntryKind::Commit => {}
+                        _ => {
+                            if seen.insert((entry.object_id(), entry_path.clone())) {
+                                objects.push(ObjectPath {
+                                    oid: entry.oid().to_string(),
+                                    path: Some(entry_path),
+                                });
+                            }
+                        }
+                    }
+                }
+
+                objects.push(ObjectPath {
+                    oid: tree_id.to_string(),
+                    path: Some(path),
+                });
+            }
+
+            Ok(objects)
+        })
+        .await
+    }
+}
+
+/// Try to resolve a revision into an object id.
+fn resolve(repo: &Repository, rev: &str) -> Result<ObjectId, GitApiError> {
+    repo.rev_parse_single(rev)
+        .map(|id| id.detach())
+        .map_err(|_| GitApiError::ObjectNotFound(rev.to_string()))
+}
+
+/// Find the object id of what lives at `path` in the tree of a commit,
+/// the tree itself for an empty path.
+fn entry_at_path(
+    repo: &Repository,
+    commit: ObjectId,
+    path: &str,
+) -> Result<Option<ObjectId>, GitApiError> {
+    let commit = repo
+        .find_commit(commit)
+        .map_err(|e| backend_error(path, e))?;
+    if path.is_empty() {
+        return Ok(Some(
+            commit
+                .tree_id()
+                .map_err(|e| backend_error(path, e))?
+                .detach(),
+        ));
+    }
+
+    Ok(commit
+        .tree()
+        .map_err(|e| backend_error(path, e))?
+        .lookup_entry_by_path(path)
+        .map_err(|e| backend_error(path, e))?
+        .map(|entry| entry.object_id()))
+}
+
+/// Read the ids of the entries of a tree by their names, none for a
+/// missing tree.
+fn tree_entries(
+    repo: &Repository,
+    tree: Option<ObjectId>,
+) -> Result<HashMap<String, ObjectId>, GitApiError> {
+    let Some(tree) = tree else {
+        return Ok(HashMap::new());
+    };
+
+    repo.find_tree(tree)
+        .map_err(|e| backend_error(tree, e))?
+        .iter()
+        .map(|entry| {
+            let entry = entry.map_err(|e| backend_error(tree, e))?;
+            Ok((entry.filename().to_string(), entry.object_id()))
+        })
+        .collect()
+}
+
+/// A request running out of time while gitoxide reads a repository, told
+/// apart from git commands timing out by the program named
+fn timed_out(repo_path: &Path) -> GitApiError {
+    GitApiError::CommandTimedOut(vec![
+        "gitoxide".to_string(),
+        repo_path.display().to_string(),
+    ])
+}
+
+/// Convert a gitoxide commit into a [`Commit`].
+fn to_commit(commit: &gix::Commit<'_>) -> Result<Commit, GitApiError> {
+    let author = commit.author().map_err(|e| backend_error(commit.id, e))?;
+    let time = author.time().map_err(|e| backend_error(commit.id, e))?;
+
+    Ok(Commit::new(
+        &commit.id.to_string(),
+        &author.name.to_string(),
+        &author.email.to_string(),
+        &to_iso_strict(time.seconds, time.offset),
+        &commit.message_raw_sloppy().to_string(),
+    ))
+}
+
+/// The name `git ls-tree` uses for the kind of an entry
+fn entry_kind_name(kind: EntryKind) -> &'static str {
+    match kind {
+        EntryKind::Tree => "tree",
+        EntryKind::Commit => "commit",
+        EntryKind::Blob | EntryKind::BlobExecutable | EntryKind::Link => "blob",
+    }
+}
+
+/// Format a git timestamp like `git log --date=iso-strict` does.
+fn to_iso_strict(seconds: i64, offset: i32) -> String {
+    FixedOffset::east_opt(offset)
+        .and_then(|tz| DateTime::from_timestamp(seconds, 0).map(|d| d.with_timezone(&tz)))
+        .map(|d| d.to_rfc3339())
+        .unwrap_or_default()
+}
+
+fn backend_error(context: impl std::fmt::Debug, err: impl std::fmt::Display) -> GitApiError {
+    GitApiError::Backend(format!("{context:?}: {err}"))
+}
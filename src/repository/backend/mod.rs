@@ -0,0 +1,354 @@
+pub mod cli;
+#[cfg(test)]
+pub mod fake;
+pub mod gitoxide;
+
+use crate::{application::GitApiError, config::BackendKind, utils::commits::Commit};
+use async_trait::async_trait;
+use serde::Serialize;
+use std::{collections::HashMap, path::Path, sync::Arc};
+
+/// The operations the api needs from git, so the way repositories
+/// are read (shelling out to git, an in-process library, an in memory
+/// fake in tests) can be swapped out without touching the models.
+///
+/// All revisions are expected to be validated by the caller.
+#[async_trait]
+pub trait GitBackend: Send + Sync {
+    /// Try to resolve a revision (branch, tag, abbreviated hash, ...)
+    /// into a full object id.
+    async fn resolve_ref(&self, repo_path: &Path, rev: &str) -> Result<String, GitApiError>;
+
+    /// Try to read the entries of a tree, or of the tree of a commit.
+    async fn read_tree(
+        &self,
+        repo_path: &Path,
+        treeish: &str,
+    ) -> Result<Vec<TreeEntry>, GitApiError>;
+
+    /// Try to find the entry at a path in the tree of a commit, `None`
+    /// when nothing lives there. The entry of an empty path is the tree
+    /// itself, with an empty name.
+    async fn entry_at(
+        &self,
+        repo_path: &Path,
+        revision: &str,
+        path: &str,
+    ) -> Result<Option<TreeEntry>, GitApiError>;
+
+    /// Try to read the raw content of a blob.
+    async fn read_blob(&self, repo_path: &Path, oid: &str) -> Result<Vec<u8>, GitApiError>;
+
+    /// Try to walk the (non merge) commits reachable from `rev`, newest first,
+    /// optionally only the ones touching `path` and at most `limit` of them.
+    async fn log(
+        &self,
+        repo_path: &Path,
+        rev: &str,
+        path: Option<&str>,
+        limit: Option<usize>,
+    ) -> Result<Vec<Commit>, GitApiError>;
+
+    /// Try to find the last (non merge) commit reachable from `rev` that
+    /// changed each of the named entries of a directory (the root when
+    /// `None`), walking the history once for all of them. Entries no
+    /// commit changed are left out. Merges that kept a directory as one
+    /// of their parents had it are only followed to that parent, like
+    /// `git log -- <directory>` does.
+    async fn last_commits(
+        &self,
+        repo_path: &Path,
+        rev: &str,
+        directory: Option<&str>,
+        names: &[String],
+    ) -> Result<HashMap<String, Commit>, GitApiError>;
+
+    /// Try to find the oldest commit without parents reachable from `rev`.
+    async fn root_commit(&self, repo_path: &Path, rev: &str)
+        -> Result<Option<Commit>, GitApiError>;
+
+    /// Try to read the branch `HEAD` points at, `None` when it's detached.
+    async fn default_branch(&self, repo_path: &Path) -> Result<Option<String>, GitApiError>;
+
+    /// Try to read every ref in the repository.
+    async fn refs(&self, repo_path: &Path) -> Result<Vec<GitRef>, GitApiError>;
+
+    /// Try to list every object reachable from any ref along with every
+    /// path it lives at in the history (`None` for commits).
+    async fn object_paths(&self, repo_path: &Path) -> Result<Vec<ObjectPath>, GitApiError>;
+}
+
+/// Create the backend selected in the configuration.
+pub fn from_kind(kind: BackendKind) -> Arc<dyn GitBackend> {
+    match kind {
+        BackendKind::Cli => Arc::new(cli::CliBackend),
+        BackendKind::Gitoxide => Arc::new(gitoxide::GitoxideBackend),
+    }
+}
+
+#[derive(Serialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+/// A model representing an entry of a tree object
+pub struct TreeEntry {
+    /// The file mode, e.g. `100644` or `040000`
+    pub mode: String,
+    /// The object type: `blob`, `tree` or `commit`
+    pub kind: String,
+    pub oid: String,
+    pub name: String,
+}
+
+#[derive(Serialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+/// A model representing a ref (branch, tag, ...) in a repository
+pub struct GitRef {
+    /// The full name of the ref, e.g. `refs/heads/master`
+    pub name: String,
+    /// The object id the ref points at
+    pub target: String,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+/// An object id along with a path it lives at in the history
+pub struct ObjectPath {
+    pub oid: String,
+    pub path: Option<String>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::Fixture;
+
+    /// Both real backends must see a repository the same way.
+    #[tokio::test]
+    async fn test_cli_and_gitoxide_backends_agree() {
+        let fixture = Fixture::new();
+        let repo = fixture.repo("alpha");
+        repo.write("README.md", "# Alpha\n")
+            .write("docs/copy of readme.md", "# Alpha\n")
+            .write("src/main.rs", "fn main() {}\n");
+        repo.commit("Initial commit");
+        repo.write("src/main.rs", "fn main() { println!(); }\n");
+        let head = repo.commit("Print something\n\nWith a body");
+
+        let cli = from_kind(BackendKind::Cli);
+        let gix = from_kind(BackendKind::Gitoxide);
+        let path = repo.bare.as_path();
+
+        assert_eq!(
+            cli.resolve_ref(path, "master").await.unwrap(),
+            gix.resolve_ref(path, "master").await.unwrap()
+        );
+        assert_eq!(cli.resolve_ref(path, "master").await.unwrap(), head);
+
+        let cli_tree = cli.read_tree(path, "master").await.unwrap();
+        assert_eq!(cli_tree, gix.read_tree(path, "master").await.unwrap());
+        assert_eq!(cli_tree.len(), 3);
+
+        let readme = &cli_tree[0].oid;
+        assert_eq!(
+            cli.read_blob(path, readme).await.unwrap(),
+            gix.read_blob(path, readme).await.unwrap()
+        );
+
+        for (entry_path, exists) in [
+            ("", true),
+            ("src", true),
+            ("docs/copy of readme.md", true),
+            ("src/missing", false),
+            ("README.md/x", false),
+        ] {
+            let cli_entry = cli.entry_at(path, &head, entry_path).await.unwrap();
+            assert_eq!(
+                cli_entry,
+                gix.entry_at(path, &head, entry_path).await.unwrap(),
+                "{entry_path}"
+            );
+            assert_eq!(cli_entry.is_some(), exists, "{entry_path}");
+        }
+
+        let summarize = |commits: Vec<Commit>| {
+            commits
+                .into_iter()
+                .map(|c| (c.hash, c.author, c.date, c.msg))
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(
+            summarize(cli.log(path, "master", None, None).await.unwrap()),
+            summarize(gix.log(path, "master", None, None).await.unwrap())
+        );
+        assert_eq!(
+            summarize(
+                cli.log(path, "master", Some("README.md"), Some(1))
+                    .await
+                    .unwrap()
+            ),
+            summarize(
+                gix.log(path, "master", Some("README.md"), Some(1))
+                    .await
+                    .unwrap()
+            )
+        );
+        // Paths aren't patterns
+        for pattern in ["*", ":(glob)**"] {
+            assert!(cli
+                .log(path, "master", Some(pattern), None)
+                .await
+                .unwrap()
+                .is_empty());
+            assert!(gix
+                .log(path, "master", Some(pattern), None)
+                .await
+                .unwrap()
+                .is_empty());
+        }
+
+        assert_eq!(
+            summarize(
+                cli.root_commit(path, "master")
+                    .await
+                    .unwrap()
+                    .into_iter()
+                    .collect()
+            ),
+            summarize(
+                gix.root_commit(path, "master")
+                    .await
+                    .unwrap()
+                    .into_iter()
+                    .collect()
+            )
+        );
+        let last_hashes = |last_commits: HashMap<String, Commit>| {
+            let mut hashes: Vec<_> = last_commits
+                .into_iter()
+                .map(|(name, commit)| (name, commit.hash))
+                .collect();
+            hashes.sort();
+            hashes
+        };
+        let names = ["README.md", "docs", "src"].map(String::from);
+        let cli_last = last_hashes(
+            cli.last_commits(path, "master", None, &names)
+                .await
+                .unwrap(),
+        );
+        assert_eq!(
+            cli_last,
+            last_hashes(
+                gix.last_commits(path, "master", None, &names)
+                    .await
+                    .unwrap()
+            )
+        );
+        assert_eq!(cli_last.len(), 3);
+        assert_eq!(cli_last[2].1, head[..6]);
+        assert_ne!(cli_last[0].1, cli_last[2].1);
+
+        let names = ["main.rs".to_string()];
+        assert_eq!(
+            last_hashes(
+                cli.last_commits(path, "master", Some("src"), &names)
+                    .await
+                    .unwrap()
+            ),
+            last_hashes(
+                gix.last_commits(path, "master", Some("src"), &names)
+                    .await
+                    .unwrap()
+            )
+        );
+
+        assert_eq!(cli.refs(path).await.unwrap(), gix.refs(path).await.unwrap());
+        assert_eq!(
+            cli.default_branch(path).await.unwrap(),
+            gix.default_branch(path).await.unwrap()
+        );
+        assert_eq!(
+            cli.default_branch(path).await.unwrap().as_deref(),
+            Some("master")
+        );
+
+        let mut cli_objects = cli.object_paths(path).await.unwrap();
+        let mut gix_objects = gix.object_paths(path).await.unwrap();
+        cli_objects.sort_by(|a, b| (&a.oid, &a.path).cmp(&(&b.oid, &b.path)));
+        gix_objects.sort_by(|a, b| (&a.oid, &a.path).cmp(&(&b.oid, &b.path)));
+        assert_eq!(cli_objects, gix_objects);
+    }
+
+    /// Merges are walked through the same way by both real backends.
+    #[tokio::test]
+    async fn test_backends_agree_on_last_commits_across_merges() {
+        let fixture = Fixture::new();
+        let repo = fixture.repo("alpha");
+        let first = repo
+            .write("README.md", "# Alpha\n")
+            .write("src/a.txt", "a\n")
+            .write("src/b.txt", "b\n")
+            .commit("first");
+        repo.git(&["checkout", "-q", "-b", "kept"]);
+        let kept = repo.write("src/a.txt", "kept\n").commit("kept");
+        repo.git(&["checkout", "-q", "-b", "dropped", "master"]);
+        repo.write("src/b.txt", "dropped\n").commit("dropped");
+        repo.git(&["checkout", "-q", "master"]);
+        repo.write("README.md", "# Alpha!\n").commit("readme");
+
+        // One merge brings its changes in, the other leaves them out
+        repo.git(&["merge", "-q", "--no-ff", "-m", "merge kept", "kept"]);
+        repo.git(&[
+            "merge",
+            "-q",
+            "-s",
+            "ours",
+            "-m",
+            "merge dropped",
+            "dropped",
+        ]);
+        repo.commit("after merges");
+
+        let cli = from_kind(BackendKind::Cli);
+        let gix = from_kind(BackendKind::Gitoxide);
+        let path = repo.bare.as_path();
+        let last_hashes = |last_commits: HashMap<String, Commit>| {
+            let mut hashes: Vec<_> = last_commits
+                .into_iter()
+                .map(|(name, commit)| (name, commit.hash))
+                .collect();
+            hashes.sort();
+            hashes
+        };
+
+        for (directory, names) in [
+            (Some("src"), ["a.txt", "b.txt"]),
+            (None, ["README.md", "src"]),
+        ] {
+            let names = names.map(String::from);
+            let cli_last = last_hashes(
+                cli.last_commits(path, "master", directory, &names)
+                    .await
+                    .unwrap(),
+            );
+            assert_eq!(
+                cli_last,
+                last_hashes(
+                    gix.last_commits(path, "master", directory, &names)
+                        .await
+                        .unwrap()
+                ),
+                "{directory:?}"
+            );
+
+            // Changes a merge left out aren't the last ones
+            if directory.is_some() {
+                assert_eq!(
+                    cli_last,
+                    [
+                        ("a.txt".to_string(), kept[..6].to_string()),
+                        ("b.txt".to_string(), first[..6].to_string()),
+                    ]
+                );
+            }
+        }
+    }
+}
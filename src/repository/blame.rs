@@ -0,0 +1,378 @@
+use crate::{
+    application::GitApiError,
+    repository::backend::GitBackend,
+    utils::{commands::run_git_command_bytes, commits::Commit, validation::validate_hash},
+};
+use chrono::{DateTime, FixedOffset};
+use serde::{Deserialize, Serialize};
+use std::{collections::HashMap, path::Path};
+
+/// The file listing the revisions blame should look through
+const IGNORE_REVS_FILE: &str = ".git-blame-ignore-revs";
+
+#[derive(Deserialize, Debug)]
+#[serde(default, rename_all = "camelCase", deny_unknown_fields)]
+/// The query string of blames, every field is optional
+pub struct BlameQuery {
+    /// The first line to blame, starting at 1
+    pub start: Option<usize>,
+    /// The last line to blame (inclusive)
+    pub end: Option<usize>,
+    /// Whether to skip the revisions listed in `.git-blame-ignore-revs`
+    pub ignore_revs: bool,
+}
+impl Default for BlameQuery {
+    fn default() -> Self {
+        BlameQuery {
+            start: None,
+            end: None,
+            ignore_revs: true,
+        }
+    }
+}
+impl BlameQuery {
+    /// Try to turn the line range into a `git blame -L` option.
+    fn line_range(&self) -> Result<Option<String>, GitApiError> {
+        match (self.start, self.end) {
+            (Some(0), _) | (_, Some(0)) => {
+                Err(GitApiError::InvalidInput("Lines start at 1".to_string()))
+            }
+            (Some(start), Some(end)) if end < start => Err(GitApiError::InvalidInput(
+                "The last line is before the first".to_string(),
+            )),
+            (None, None) => Ok(None),
+            (start, end) => Ok(Some(format!(
+                "-L{},{}",
+                start.unwrap_or(1),
+                end.map(|end| end.to_string()).unwrap_or_default()
+            ))),
+        }
+    }
+}
+
+#[derive(Serialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+/// Consecutive lines last changed by the same commit
+pub struct BlameHunk {
+    pub commit: Commit,
+    /// The number of the first line in the file at the revision
+    pub start_line: usize,
+    /// The number of the first line in the file at the commit
+    pub original_start_line: usize,
+    /// The path of the file at the commit, different if it was since renamed
+    pub original_path: String,
+    pub lines: Vec<String>,
+}
+
+#[derive(Serialize, Debug)]
+#[serde(rename_all = "camelCase")]
+/// A model representing who last changed every line of a file
+pub struct Blame {
+    pub path: String,
+    /// The commit the file was blamed at
+    pub revision: String,
+    /// Whether the revisions in `.git-blame-ignore-revs` were skipped
+    pub ignored_revs: bool,
+    pub hunks: Vec<BlameHunk>,
+}
+impl Blame {
+    /// Try to blame a file at a revision.
+    pub async fn of_file(
+        backend: &dyn GitBackend,
+        repo_path: &Path,
+        rev: &str,
+        path: &str,
+        query: &BlameQuery,
+    ) -> Result<Blame, GitApiError> {
+        let line_range = query.line_range()?;
+        let revision = backend
+            .resolve_ref(repo_path, &format!("{rev}^{{commit}}"))
+            .await?;
+
+        // Make sure the file exists so a missing one isn't a failure
+        if blob_at(backend, repo_path, &revision, path)
+            .await?
+            .is_none()
+        {
+            return Err(GitApiError::ObjectNotFound(path.to_string()));
+        }
+
+        // The repositories are bare, so the ignore list is read from the
+        // revision and handed to git a revision at a time
+        let ignore_revs = if query.ignore_revs {
+            Self::ignore_revs(backend, repo_path, &revision).await?
+        } else {
+            None
+        };
+
+        let mut args = vec!["blame", "--porcelain"];
+        for rev in ignore_revs.iter().flatten() {
+            args.extend(["--ignore-rev", rev]);
+        }
+        args.extend(line_range.as_deref());
+        args.extend([revision.as_str(), "--", path]);
+
+        let output = match run_git_command_bytes(repo_path, &args).await {
+            Ok(output) => output,
+            Err(GitApiError::CommandFailed(failure)) if failure.stderr.contains("has only") => {
+                return Err(GitApiError::InvalidInput(failure.stderr));
+            }
+            Err(GitApiError::CommandFailed(failure)) => {
+                return Err(match unknown_ignored_rev(&failure.stderr) {
+                    Some(rev) => GitApiError::InvalidInput(format!(
+                        "{IGNORE_REVS_FILE} lists {rev}, which isn't a commit"
+                    )),
+                    None => GitApiError::CommandFailed(failure),
+                });
+            }
+            Err(e) => return Err(e),
+        };
+
+        Ok(Blame {
+            path: path.to_string(),
+            revision,
+            ignored_revs: ignore_revs.is_some(),
+            hunks: parse_porcelain(&String::from_utf8_lossy(&output)),
+        })
+    }
+
+    /// Try to read the revisions the ignore list of a revision names,
+    /// `None` when it has none.
+    async fn ignore_revs(
+        backend: &dyn GitBackend,
+        repo_path: &Path,
+        revision: &str,
+    ) -> Result<Option<Vec<String>>, GitApiError> {
+        let Some(oid) = blob_at(backend, repo_path, revision, IGNORE_REVS_FILE).await? else {
+            return Ok(None);
+        };
+        let contents = backend.read_blob(repo_path, &oid).await?;
+
+        parse_ignore_revs(&String::from_utf8_lossy(&contents)).map(Some)
+    }
+}
+
+/// Try to get the id of the file at a path in a revision, `None` when
+/// there's no file there.
+pub async fn blob_at(
+    backend: &dyn GitBackend,
+    repo_path: &Path,
+    revision: &str,
+    path: &str,
+) -> Result<Option<String>, GitApiError> {
+    let entry = backend.entry_at(repo_path, revision, path).await?;
+    Ok(entry.and_then(|entry| (entry.kind == "blob").then_some(entry.oid)))
+}
+
+/// Try to parse an ignore list like git does, a revision per line and
+/// `#` starting a comment, every revision having to be a hash.
+fn parse_ignore_revs(contents: &str) -> Result<Vec<String>, GitApiError> {
+    contents
+        .lines()
+        .map(|line| line.split('#').next().unwrap_or_default().trim())
+        .filter(|rev| !rev.is_empty())
+        .map(|rev| {
+            validate_hash(rev).map(str::to_string).map_err(|_| {
+                GitApiError::InvalidInput(format!(
+                    "{IGNORE_REVS_FILE} lists {rev}, which isn't a hash"
+                ))
+            })
+        })
+        .collect()
+}
+
+/// Find the revision git couldn't ignore in the error it reported.
+fn unknown_ignored_rev(stderr: &str) -> Option<&str> {
+    let (_, rest) = stderr.split_once("cannot find revision ")?;
+    let (rev, _) = rest.split_once(" to ignore")?;
+
+    Some(rev)
+}
+
+/// Parse `git blame --porcelain` output into hunks.
+///
+/// Every line starts with `<hash> <original line> <line>`, followed by
+/// the commit's headers the first time it shows up, and then the line
+/// itself prefixed with a tab.
+fn parse_porcelain(output: &str) -> Vec<BlameHunk> {
+    let mut commits: HashMap<&str, Commit> = HashMap::new();
+    let mut headers: HashMap<&str, &str> = HashMap::new();
+    let mut hunks: Vec<BlameHunk> = Vec::new();
+    let mut current: Option<(&str, usize, usize)> = None;
+    // Only the first line of every group says which file it came from
+    let mut filename = "";
+    let mut previous_hash = None;
+
+    for line in output.lines() {
+        if let Some(content) = line.strip_prefix('\t') {
+            let Some((hash, original_line, final_line)) = current.take() else {
+                continue;
+            };
+            let commit = commits
+                .entry(hash)
+                .or_insert_with(|| commit_from_headers(hash, &headers))
+                .clone();
+            headers.clear();
+
+            // Extend the previous hunk when the lines follow each other
+            let same_commit = previous_hash.replace(hash) == Some(hash);
+            if let Some(hunk) = hunks.last_mut().filter(|_| same_commit) {
+                let follows = hunk.start_line + hunk.lines.len() == final_line
+                    && hunk.original_start_line + hunk.lines.len() == original_line;
+                if follows {
+                    hunk.lines.push(content.to_string());
+                    continue;
+                }
+            }
+            hunks.push(BlameHunk {
+                commit,
+                start_line: final_line,
+                original_start_line: original_line,
+                original_path: filename.to_string(),
+                lines: vec![content.to_string()],
+            });
+        } else if current.is_none() {
+            let mut parts = line.split(' ');
+            if let (Some(hash), Some(Ok(original_line)), Some(Ok(final_line))) = (
+                parts.next(),
+                parts.next().map(str::parse),
+                parts.next().map(str::parse),
+            ) {
+                current = Some((hash, original_line, final_line));
+            }
+        } else if let Some((key, value)) = line.split_once(' ') {
+            if key == "filename" {
+                filename = value;
+            }
+            headers.insert(key, value);
+        }
+    }
+
+    hunks
+}
+
+/// Build the commit of a blamed line from its porcelain headers.
+fn commit_from_headers(hash: &str, headers: &HashMap<&str, &str>) -> Commit {
+    let header = |key: &str| headers.get(key).copied().unwrap_or("");
+
+    // Dates are a unix timestamp and an offset like "+0200"
+    let date = header("author-time")
+        .parse::<i64>()
+        .ok()
+        .zip(parse_offset(header("author-tz")))
+        .and_then(|(time, offset)| {
+            DateTime::from_timestamp(time, 0).map(|d| d.with_timezone(&offset))
+        })
+        .unwrap_or_default()
+        .to_rfc3339();
+
+    Commit::new(
+        hash,
+        header("author"),
+        header("author-mail")
+            .trim_start_matches('<')
+            .trim_end_matches('>'),
+        &date,
+        header("summary"),
+    )
+}
+
+/// Parse a timezone offset like `+0200` or `-0530`.
+fn parse_offset(offset: &str) -> Option<FixedOffset> {
+    let sign = match offset.get(..1)? {
+        "+" => 1,
+        "-" => -1,
+        _ => return None,
+    };
+    let hours: i32 = offset.get(1..3)?.parse().ok()?;
+    let minutes: i32 = offset.get(3..5)?.parse().ok()?;
+
+    FixedOffset::east_opt(sign * (hours * 3600 + minutes * 60))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PORCELAIN: &str = "aaaaaaaa 1 1 2
+author Ada
+author-mail <ada@example.com>
+author-time 1700000000
+author-tz +0100
+summary First
+filename old.txt
+\tone
+aaaaaaaa 2 2
+\ttwo
+bbbbbbbb 3 3 1
+author Bob
+author-mail <bob@example.com>
+author-time 1700003600
+author-tz -0530
+summary Second
+filename new.txt
+\tthree
+aaaaaaaa 5 4 1
+filename old.txt
+\tfour
+";
+
+    #[test]
+    fn test_parse_porcelain_groups_lines_into_hunks() {
+        let hunks = parse_porcelain(PORCELAIN);
+        assert_eq!(hunks.len(), 3);
+
+        assert_eq!(hunks[0].commit.hash, "aaaaaa");
+        assert_eq!(hunks[0].commit.author, "Ada");
+        assert_eq!(hunks[0].commit.author_email, "ada@example.com");
+        assert_eq!(hunks[0].commit.msg, "First");
+        assert_eq!(hunks[0].commit.date, "11/14/2023 23:13");
+        assert_eq!(hunks[0].original_path, "old.txt");
+        assert_eq!(hunks[0].lines, ["one", "two"]);
+
+        assert_eq!(hunks[1].commit.author, "Bob");
+        assert_eq!(hunks[1].start_line, 3);
+
+        // Same commit, but not where the last hunk of it left off
+        assert_eq!(hunks[2].commit.author, "Ada");
+        assert_eq!((hunks[2].start_line, hunks[2].original_start_line), (4, 5));
+    }
+
+    #[test]
+    fn test_parse_ignore_revs() {
+        let contents = "# Formatting\nabcdef1234\n\n  0123456789abcdef  # Renames\n";
+        assert_eq!(
+            parse_ignore_revs(contents).unwrap(),
+            ["abcdef1234", "0123456789abcdef"]
+        );
+
+        let Err(GitApiError::InvalidInput(message)) = parse_ignore_revs("abcdef\n--all\n") else {
+            panic!("Options must not be passed along");
+        };
+        assert!(message.contains("--all"), "{message}");
+
+        assert_eq!(
+            unknown_ignored_rev("fatal: cannot find revision abcdef to ignore"),
+            Some("abcdef")
+        );
+    }
+
+    #[test]
+    fn test_line_ranges() {
+        let range = |start, end| {
+            BlameQuery {
+                start,
+                end,
+                ..BlameQuery::default()
+            }
+            .line_range()
+        };
+
+        assert_eq!(range(None, None).unwrap(), None);
+        assert_eq!(range(Some(2), Some(4)).unwrap().unwrap(), "-L2,4");
+        assert_eq!(range(Some(2), None).unwrap().unwrap(), "-L2,");
+        assert_eq!(range(None, Some(4)).unwrap().unwrap(), "-L1,4");
+        assert!(range(Some(0), None).is_err());
+        assert!(range(Some(4), Some(2)).is_err());
+    }
+}
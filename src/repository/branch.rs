@@ -0,0 +1,125 @@
+use crate::{
+    application::GitApiError,
+    repository::backend::GitBackend,
+    utils::{
+        commands::run_git_command,
+        commits::Commit,
+        pagination::{paginate, PageInfo, PageQuery},
+    },
+};
+use serde::Serialize;
+use std::path::Path;
+
+/// The `git for-each-ref` format every [`Branch`] is parsed from
+const BRANCH_FORMAT: &str = "--format=%(refname:short)%1f%(objectname)%1f%(authorname)%1f%(authoremail:trim)%1f%(authordate:iso-strict)%1f%(contents:subject)";
+
+#[derive(Serialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+/// A model representing a branch in a repository
+pub struct Branch {
+    pub name: String,
+    /// The commit at the tip of the branch
+    pub commit: Commit,
+    pub is_default: bool,
+    /// Commits on the branch that aren't on the default branch
+    pub ahead: Option<usize>,
+    /// Commits on the default branch that aren't on the branch
+    pub behind: Option<usize>,
+}
+impl Branch {
+    /// Try to get a page of the branches of a repository, the default
+    /// branch first and then the most recently committed to.
+    pub async fn get_page(
+        backend: &dyn GitBackend,
+        repo_path: &Path,
+        query: &PageQuery,
+    ) -> Result<BranchPage, GitApiError> {
+        let default_branch = backend.default_branch(repo_path).await?;
+
+        let output = run_git_command(
+            repo_path,
+            &[
+                "for-each-ref",
+                "--sort=-committerdate",
+                BRANCH_FORMAT,
+                "refs/heads",
+            ],
+            false,
+        )
+        .await?;
+
+        let mut branches: Vec<Branch> = output
+            .lines()
+            .filter_map(|line| Self::parse(line, default_branch.as_deref()))
+            .collect();
+        branches.sort_by_key(|branch| !branch.is_default);
+
+        // Commits can only be counted when the default branch has any
+        let base = default_branch
+            .as_deref()
+            .filter(|_| branches.first().is_some_and(|branch| branch.is_default));
+
+        // Only count commits for the branches on the page
+        let (mut branches, page) = paginate(branches, query.page, query.per_page)?;
+        if let Some(base) = base {
+            for branch in branches.iter_mut() {
+                let (ahead, behind) = if branch.is_default {
+                    (0, 0)
+                } else {
+                    Self::ahead_behind(repo_path, base, &branch.name).await?
+                };
+                branch.ahead = Some(ahead);
+                branch.behind = Some(behind);
+            }
+        }
+
+        Ok(BranchPage { branches, page })
+    }
+
+    /// Parse a line of `git for-each-ref` output in [`BRANCH_FORMAT`].
+    fn parse(line: &str, default_branch: Option<&str>) -> Option<Branch> {
+        let fields: Vec<&str> = line.split('\x1f').collect();
+        let [name, hash, author, author_email, date, subject] = fields.as_slice() else {
+            return None;
+        };
+
+        Some(Branch {
+            name: name.to_string(),
+            commit: Commit::new(hash, author, author_email, date, subject),
+            is_default: default_branch == Some(*name),
+            ahead: None,
+            behind: None,
+        })
+    }
+
+    /// Try to count the commits only on `branch` and only on `base`.
+    async fn ahead_behind(
+        repo_path: &Path,
+        base: &str,
+        branch: &str,
+    ) -> Result<(usize, usize), GitApiError> {
+        let range = format!("refs/heads/{base}...refs/heads/{branch}");
+        let output = run_git_command(
+            repo_path,
+            &["rev-list", "--left-right", "--count", &range, "--"],
+            false,
+        )
+        .await?;
+
+        // The output is "<only on base>\t<only on branch>"
+        let mut counts = output.split_whitespace().map(str::parse::<usize>);
+        match (counts.next(), counts.next()) {
+            (Some(Ok(behind)), Some(Ok(ahead))) => Ok((ahead, behind)),
+            _ => Err(GitApiError::Backend(format!("Bad commit counts {output}"))),
+        }
+    }
+}
+
+#[derive(Serialize, Debug)]
+#[serde(rename_all = "camelCase")]
+/// A model representing a page of the branches of a repository
+pub struct BranchPage {
+    pub branches: Vec<Branch>,
+    #[serde(flatten)]
+    pub page: PageInfo,
+}
@@ -0,0 +1,343 @@
+use crate::{
+    application::GitApiError,
+    repository::backend::{cli::LOG_FORMAT, GitBackend},
+    utils::{commands::run_git_command, commits::Commit, dates::parse_date_to_string},
+};
+use serde::Serialize;
+use std::{collections::HashMap, path::Path};
+
+/// The `git show` format every [`CommitDetail`] is parsed from, trailers
+/// separated by `\x1d` and their keys and values by `\x1c`
+const DETAIL_FORMAT: &str = concat!(
+    "--format=%H%x1f%h%x1f%P",
+    "%x1f%an%x1f%ae%x1f%aI%x1f%cn%x1f%ce%x1f%cI",
+    "%x1f%(trailers:only,unfold,separator=%x1d,key_value_separator=%x1c)%x1f%B",
+);
+
+#[derive(Serialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+/// Who authored or committed a commit, and when
+pub struct Identity {
+    pub name: String,
+    pub email: String,
+    pub date: String,
+}
+impl Identity {
+    fn parse(name: &str, email: &str, date: &str) -> Identity {
+        Identity {
+            name: name.to_string(),
+            email: email.to_string(),
+            date: parse_date_to_string(date.to_string()),
+        }
+    }
+}
+
+#[derive(Serialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+/// A `Key: value` trailer at the end of a commit message, like `Signed-off-by`
+pub struct Trailer {
+    pub key: String,
+    pub value: String,
+}
+
+#[derive(Serialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "camelCase")]
+/// How a file changed in a commit
+pub enum ChangeStatus {
+    Added,
+    Modified,
+    Deleted,
+    Renamed,
+    Copied,
+    /// The file became a symlink or submodule, or the other way around
+    TypeChanged,
+}
+impl ChangeStatus {
+    /// Parse the status letter of `git diff-tree --raw` or `--name-status`.
+    pub fn parse(status: &str) -> Option<ChangeStatus> {
+        match status.get(..1)? {
+            "A" => Some(ChangeStatus::Added),
+            "M" => Some(ChangeStatus::Modified),
+            "D" => Some(ChangeStatus::Deleted),
+            "R" => Some(ChangeStatus::Renamed),
+            "C" => Some(ChangeStatus::Copied),
+            "T" => Some(ChangeStatus::TypeChanged),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Serialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+/// A file changed by a commit, or between two commits
+pub struct FileChange {
+    pub path: String,
+    /// Where the file was renamed or copied from
+    pub old_path: Option<String>,
+    pub status: ChangeStatus,
+    /// Lines added, `None` for binary files
+    pub additions: Option<usize>,
+    /// Lines deleted, `None` for binary files
+    pub deletions: Option<usize>,
+    pub binary: bool,
+}
+impl FileChange {
+    /// Try to list the files changed between two commits, or by a root
+    /// commit when there's nothing to compare it to.
+    pub async fn between(
+        repo_path: &Path,
+        from: Option<&str>,
+        to: &str,
+    ) -> Result<Vec<FileChange>, GitApiError> {
+        // Raw records come first, then the line counts of every file
+        let mut args = vec![
+            "diff-tree",
+            "-r",
+            "-z",
+            "-M",
+            "--no-commit-id",
+            "--raw",
+            "--numstat",
+        ];
+        match from {
+            Some(from) => args.extend([from, to]),
+            None => args.extend(["--root", to]),
+        }
+        let output = run_git_command(repo_path, &args, false).await?;
+
+        Ok(parse_raw_numstat(&output))
+    }
+}
+
+#[derive(Serialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+/// A model representing everything about a single commit
+pub struct CommitDetail {
+    pub hash: String,
+    pub short_hash: String,
+    pub parents: Vec<String>,
+    pub author: Identity,
+    pub committer: Identity,
+    /// The first line of the message
+    pub subject: String,
+    /// The full message, trailers included
+    pub message: String,
+    pub trailers: Vec<Trailer>,
+    /// The files changed compared to the first parent
+    pub files: Vec<FileChange>,
+}
+impl CommitDetail {
+    /// Try to get the details of a commit by a (possibly abbreviated) hash.
+    pub async fn by_hash(
+        backend: &dyn GitBackend,
+        repo_path: &Path,
+        hash: &str,
+    ) -> Result<CommitDetail, GitApiError> {
+        // Peeling to a commit rejects hashes of any other kind of object
+        let oid = backend
+            .resolve_ref(repo_path, &format!("{hash}^{{commit}}"))
+            .await?;
+
+        let output =
+            run_git_command(repo_path, &["show", "-s", DETAIL_FORMAT, &oid], false).await?;
+        let mut detail = Self::parse(&output)
+            .ok_or_else(|| GitApiError::Backend(format!("Bad commit {oid}")))?;
+        let parent = detail.parents.first().map(String::as_str);
+        detail.files = FileChange::between(repo_path, parent, &oid).await?;
+
+        Ok(detail)
+    }
+
+    /// Parse the output of `git show` in [`DETAIL_FORMAT`], without files.
+    fn parse(output: &str) -> Option<CommitDetail> {
+        let mut fields = output.splitn(11, '\x1f');
+        let mut next = || fields.next();
+        let (hash, short_hash, parents) = (next()?, next()?, next()?);
+        let author = Identity::parse(next()?, next()?, next()?);
+        let committer = Identity::parse(next()?, next()?, next()?);
+        let (trailers, message) = (next()?, next()?);
+
+        let message = message.trim_end().to_string();
+        Some(CommitDetail {
+            hash: hash.to_string(),
+            short_hash: short_hash.to_string(),
+            parents: parents.split_whitespace().map(str::to_string).collect(),
+            author,
+            committer,
+            subject: message.lines().next().unwrap_or("").to_string(),
+            trailers: trailers
+                .split('\x1d')
+                .filter_map(|trailer| trailer.split_once('\x1c'))
+                .map(|(key, value)| Trailer {
+                    key: key.trim().to_string(),
+                    value: value.trim().to_string(),
+                })
+                .collect(),
+            message,
+            files: Vec::new(),
+        })
+    }
+}
+
+/// Try to get the newest (non merge) commits in a range like `a..b`.
+pub async fn commits_in_range(
+    repo_path: &Path,
+    range: &str,
+    limit: usize,
+) -> Result<Vec<Commit>, GitApiError> {
+    let limit = format!("--max-count={limit}");
+    let log_output = run_git_command(
+        repo_path,
+        &[
+            "log",
+            "--no-merges",
+            "--date=iso-strict",
+            LOG_FORMAT,
+            &limit,
+            range,
+            "--",
+        ],
+        false,
+    )
+    .await?;
+
+    Ok(log_output
+        .split('\x1e')
+        .filter(|part| !part.trim().is_empty())
+        .map(Commit::from)
+        .collect())
+}
+
+/// Try to count the (non merge) commits in a range like `a..b`.
+pub async fn count_commits(repo_path: &Path, range: &str) -> Result<usize, GitApiError> {
+    let output = run_git_command(
+        repo_path,
+        &["rev-list", "--count", "--no-merges", range, "--"],
+        false,
+    )
+    .await?;
+
+    output
+        .trim()
+        .parse()
+        .map_err(|_| GitApiError::Backend(format!("Bad commit count {output}")))
+}
+
+/// Parse `git diff-tree -z --raw --numstat` output into the changed
+/// files. Line counts are matched to files by path rather than by
+/// position, as a file can be counted more than once (like when its type
+/// changed) and the counts of a path are added up.
+fn parse_raw_numstat(output: &str) -> Vec<FileChange> {
+    let mut fields = output.split('\0').filter(|field| !field.is_empty());
+    let mut changes = Vec::new();
+    let mut counts: HashMap<&str, (Option<usize>, Option<usize>)> = HashMap::new();
+
+    while let Some(field) = fields.next() {
+        // A raw record, like `:100644 100644 <oid> <oid> M`
+        if let Some(raw) = field.strip_prefix(':') {
+            let Some(status) = raw.rsplit(' ').next().and_then(ChangeStatus::parse) else {
+                continue;
+            };
+            let Some(path) = fields.next() else { break };
+
+            let (path, old_path) = match status {
+                ChangeStatus::Renamed | ChangeStatus::Copied => {
+                    let Some(new_path) = fields.next() else { break };
+                    (new_path, Some(path.to_string()))
+                }
+                _ => (path, None),
+            };
+            changes.push((status, path, old_path));
+            continue;
+        }
+
+        // Line counts, like `1\t2\tpath`, binary files counting `-`
+        let mut parts = field.splitn(3, '\t');
+        let (Some(additions), Some(deletions), Some(path)) =
+            (parts.next(), parts.next(), parts.next())
+        else {
+            continue;
+        };
+        // Renames and copies list both paths as separate fields
+        let path = if path.is_empty() {
+            fields.next();
+            fields.next().unwrap_or_default()
+        } else {
+            path
+        };
+
+        let add = |total: Option<usize>, count: &str| Some(total? + count.parse::<usize>().ok()?);
+        let total = counts.entry(path).or_insert((Some(0), Some(0)));
+        *total = (add(total.0, additions), add(total.1, deletions));
+    }
+
+    changes
+        .into_iter()
+        .map(|(status, path, old_path)| {
+            let (additions, deletions) = counts.get(path).copied().unwrap_or((Some(0), Some(0)));
+
+            FileChange {
+                path: path.to_string(),
+                old_path,
+                status,
+                additions,
+                deletions,
+                binary: additions.is_none(),
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_diff_tree_output() {
+        let output = concat!(
+            ":100644 100644 aaa aaa R100\0a\0b\0",
+            ":120000 100644 bbb ccc T\0link\0",
+            ":100644 100644 ddd eee M\0bin\0",
+            ":000000 100644 000 fff A\0c\0",
+            "0\t0\t\0a\0b\0",
+            "0\t1\tlink\0",
+            "2\t0\tlink\0",
+            "-\t-\tbin\0",
+            "1\t0\tc\0",
+        );
+
+        let changes: Vec<_> = parse_raw_numstat(output)
+            .into_iter()
+            .map(|change| {
+                (
+                    change.status,
+                    change.path,
+                    change.old_path,
+                    change.additions,
+                    change.deletions,
+                )
+            })
+            .collect();
+        assert_eq!(
+            changes,
+            [
+                (
+                    ChangeStatus::Renamed,
+                    "b".to_string(),
+                    Some("a".to_string()),
+                    Some(0),
+                    Some(0)
+                ),
+                (
+                    ChangeStatus::TypeChanged,
+                    "link".to_string(),
+                    None,
+                    Some(2),
+                    Some(1)
+                ),
+                (ChangeStatus::Modified, "bin".to_string(), None, None, None),
+                (ChangeStatus::Added, "c".to_string(), None, Some(1), Some(0)),
+            ]
+        );
+    }
+}
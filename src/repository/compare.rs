@@ -0,0 +1,100 @@
+use crate::{
+    application::GitApiError,
+    repository::{
+        backend::GitBackend,
+        commit::{commits_in_range, count_commits, FileChange},
+    },
+    utils::{commands::run_git_command, commits::Commit},
+};
+use serde::Serialize;
+use std::path::Path;
+
+/// The most commits listed in a comparison
+pub const COMPARE_COMMIT_LIMIT: usize = 250;
+
+#[derive(Serialize, Debug, Clone, Default)]
+#[serde(rename_all = "camelCase")]
+/// The size of the changes in a comparison
+pub struct DiffStats {
+    pub files_changed: usize,
+    pub additions: usize,
+    pub deletions: usize,
+}
+
+#[derive(Serialize, Debug)]
+#[serde(rename_all = "camelCase")]
+/// A model representing what a head revision would bring into a base
+/// revision, like a pull request
+pub struct Comparison {
+    /// The commit the base revision points at
+    pub base: String,
+    /// The commit the head revision points at
+    pub head: String,
+    /// The newest common ancestor, `None` for unrelated histories
+    pub merge_base: Option<String>,
+    /// How many (non merge) commits head has that base doesn't
+    pub ahead_by: usize,
+    /// How many (non merge) commits base has that head doesn't
+    pub behind_by: usize,
+    /// The newest of the commits head is ahead by, at most [`COMPARE_COMMIT_LIMIT`]
+    pub commits: Vec<Commit>,
+    pub stats: DiffStats,
+    /// The files changed on head since the merge base
+    pub files: Vec<FileChange>,
+}
+impl Comparison {
+    /// Try to compare two revisions.
+    pub async fn between(
+        backend: &dyn GitBackend,
+        repo_path: &Path,
+        base: &str,
+        head: &str,
+    ) -> Result<Comparison, GitApiError> {
+        let base = backend
+            .resolve_ref(repo_path, &format!("{base}^{{commit}}"))
+            .await?;
+        let head = backend
+            .resolve_ref(repo_path, &format!("{head}^{{commit}}"))
+            .await?;
+        let merge_base = Self::merge_base(repo_path, &base, &head).await?;
+
+        let ahead_by = count_commits(repo_path, &format!("{base}..{head}")).await?;
+        let behind_by = count_commits(repo_path, &format!("{head}..{base}")).await?;
+        let commits =
+            commits_in_range(repo_path, &format!("{base}..{head}"), COMPARE_COMMIT_LIMIT).await?;
+
+        // Only show what changed on head, unless nothing is shared
+        let from = merge_base.as_deref().unwrap_or(&base);
+        let files = FileChange::between(repo_path, Some(from), &head).await?;
+        let stats = DiffStats {
+            files_changed: files.len(),
+            additions: files.iter().filter_map(|file| file.additions).sum(),
+            deletions: files.iter().filter_map(|file| file.deletions).sum(),
+        };
+
+        Ok(Comparison {
+            base,
+            head,
+            merge_base,
+            ahead_by,
+            behind_by,
+            commits,
+            stats,
+            files,
+        })
+    }
+
+    /// Try to find the newest common ancestor of two commits.
+    pub async fn merge_base(
+        repo_path: &Path,
+        base: &str,
+        head: &str,
+    ) -> Result<Option<String>, GitApiError> {
+        // Commits without a common ancestor make it exit with 1 and no message
+        match run_git_command(repo_path, &["merge-base", base, head], false).await {
+            Ok(oid) => Ok(Some(oid.trim().to_string())),
+            Err(GitApiError::CommandFailed(failure)) if failure.status == Some(1) => Ok(None),
+            Err(e) => Err(e),
+        }
+    }
+}
@@ -0,0 +1,575 @@
+use crate::{
+    application::GitApiError,
+    repository::{
+        backend::GitBackend,
+        commit::ChangeStatus,
+        intraline::{self, Span, Tokenizer},
+    },
+    utils::commands::{run_git_command_bytes, stream_git_command},
+};
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+
+/// Lines of context shown around changes when none are asked for
+pub const DEFAULT_CONTEXT: usize = 3;
+/// The most lines of context a diff can be asked for
+pub const MAX_CONTEXT: usize = 1000;
+/// The most files a diff lists
+pub const MAX_FILES: usize = 1000;
+/// The most bytes of patches loaded for a diff, files whose patches come
+/// after them being left out
+pub const MAX_PATCH_BYTES: usize = 2 * 1024 * 1024;
+
+#[derive(Deserialize, Debug, Clone, Copy, Default, PartialEq)]
+#[serde(rename_all = "camelCase")]
+/// Which whitespace changes are ignored when comparing lines
+pub enum Whitespace {
+    #[default]
+    Show,
+    /// Ignore all whitespace (`git diff -w`)
+    IgnoreAll,
+    /// Ignore changes in the amount of whitespace (`git diff -b`)
+    IgnoreChange,
+    /// Ignore whitespace at the end of lines
+    IgnoreEol,
+}
+
+#[derive(Deserialize, Debug, Default)]
+#[serde(default, rename_all = "camelCase", deny_unknown_fields)]
+/// The query string of diffs, every field is optional
+pub struct DiffQuery {
+    /// Lines of context around changes, [`DEFAULT_CONTEXT`] when omitted
+    pub context: Option<usize>,
+    pub whitespace: Whitespace,
+    /// How to find what changed within replaced lines
+    pub intraline: Tokenizer,
+}
+impl DiffQuery {
+    /// Try to turn the query into `git diff-tree` options.
+    fn args(&self) -> Result<Vec<String>, GitApiError> {
+        let context = self.context.unwrap_or(DEFAULT_CONTEXT);
+        if context > MAX_CONTEXT {
+            return Err(GitApiError::InvalidInput(format!(
+                "Context must be at most {MAX_CONTEXT} lines"
+            )));
+        }
+
+        let mut args = vec![format!("--unified={context}")];
+        match self.whitespace {
+            Whitespace::Show => {}
+            Whitespace::IgnoreAll => args.push("--ignore-all-space".to_string()),
+            Whitespace::IgnoreChange => args.push("--ignore-space-change".to_string()),
+            Whitespace::IgnoreEol => args.push("--ignore-space-at-eol".to_string()),
+        }
+
+        Ok(args)
+    }
+}
+
+#[derive(Serialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub enum LineKind {
+    Context,
+    Added,
+    Deleted,
+}
+
+#[derive(Serialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+/// A single line of a hunk
+pub struct DiffLine {
+    pub kind: LineKind,
+    /// The line number in the old file, `None` for added lines
+    pub old_line: Option<usize>,
+    /// The line number in the new file, `None` for deleted lines
+    pub new_line: Option<usize>,
+    pub content: String,
+    /// What changed within the line, when it replaced (or was replaced
+    /// by) a similar line
+    pub changes: Vec<Span>,
+}
+
+#[derive(Serialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+/// A block of changed lines and their context
+pub struct Hunk {
+    pub old_start: usize,
+    pub old_lines: usize,
+    pub new_start: usize,
+    pub new_lines: usize,
+    /// The function or section the hunk is in, as guessed by git
+    pub section: String,
+    pub lines: Vec<DiffLine>,
+}
+
+#[derive(Serialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+/// The changes made to a single file
+pub struct FileDiff {
+    pub path: String,
+    /// Where the file was renamed or copied from
+    pub old_path: Option<String>,
+    pub status: ChangeStatus,
+    /// The mode of the file before, `None` when it was added
+    pub old_mode: Option<String>,
+    /// The mode of the file after, `None` when it was deleted
+    pub new_mode: Option<String>,
+    /// Binary files have no hunks
+    pub binary: bool,
+    pub additions: usize,
+    pub deletions: usize,
+    pub hunks: Vec<Hunk>,
+}
+
+#[derive(Serialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+/// A model representing the changes between two commits
+pub struct Diff {
+    /// The commit compared against, `None` for the diff of a root commit
+    pub from: Option<String>,
+    pub to: String,
+    pub files: Vec<FileDiff>,
+    /// Whether files were left out to stay within [`MAX_FILES`] and
+    /// [`MAX_PATCH_BYTES`]
+    pub truncated: bool,
+}
+impl Diff {
+    /// Try to get the changes between two revisions.
+    pub async fn between(
+        backend: &dyn GitBackend,
+        repo_path: &Path,
+        from: &str,
+        to: &str,
+        query: &DiffQuery,
+    ) -> Result<Diff, GitApiError> {
+        let from = resolve_commit(backend, repo_path, from).await?;
+        let to = resolve_commit(backend, repo_path, to).await?;
+
+        let (files, truncated) = Self::files(repo_path, &[&from, &to], query).await?;
+
+        Ok(Diff {
+            from: Some(from),
+            to,
+            files,
+            truncated,
+        })
+    }
+
+    /// Try to get the changes a commit made compared to its first parent.
+    pub async fn of_commit(
+        backend: &dyn GitBackend,
+        repo_path: &Path,
+        rev: &str,
+        query: &DiffQuery,
+    ) -> Result<Diff, GitApiError> {
+        let to = resolve_commit(backend, repo_path, rev).await?;
+        let parent = resolve_commit(backend, repo_path, &format!("{to}^1"))
+            .await
+            .ok();
+
+        // Root commits are compared to nothing, merges to their first parent
+        let (files, truncated) = Self::files(
+            repo_path,
+            &["--root", "--diff-merges=first-parent", &to],
+            query,
+        )
+        .await?;
+
+        Ok(Diff {
+            from: parent,
+            to,
+            files,
+            truncated,
+        })
+    }
+
+    /// Try to diff the trees given to `git diff-tree`, once for the file
+    /// list (with unambiguous paths) and once for the patches, along with
+    /// whether files were left out to stay within the limits.
+    async fn files(
+        repo_path: &Path,
+        trees: &[&str],
+        query: &DiffQuery,
+    ) -> Result<(Vec<FileDiff>, bool), GitApiError> {
+        let options = query.args()?;
+        let diff_tree = |format: &'static [&'static str]| -> Vec<&str> {
+            // Only quote paths with control characters, quotes or backslashes
+            [
+                "-c",
+                "core.quotePath=false",
+                "diff-tree",
+                "-r",
+                "--no-commit-id",
+                "-M",
+                "-C",
+            ]
+            .into_iter()
+            .chain(format.iter().copied())
+            .chain(options.iter().map(String::as_str))
+            .chain(trees.iter().copied())
+            .collect()
+        };
+        let raw = run_git_command_bytes(repo_path, &diff_tree(&["--raw", "-z"])).await?;
+
+        // Patches are only read up to the limit, git being stopped there
+        let mut patch = String::new();
+        stream_git_command(
+            repo_path,
+            &diff_tree(&["--patch", "--full-index"]),
+            b'\n',
+            |line| {
+                patch.push_str(line);
+                patch.push('\n');
+                patch.len() <= MAX_PATCH_BYTES
+            },
+        )
+        .await?;
+        let patch_cut = patch.len() > MAX_PATCH_BYTES;
+
+        // Both list the files in the same order, but files whose changes
+        // are all ignored (like whitespace) have no patch
+        let raw = String::from_utf8_lossy(&raw);
+        let mut patches = split_patches(&patch);
+        if patch_cut {
+            // The last patch was only partly read
+            patches.pop();
+        }
+        let mut patches = patches.into_iter().peekable();
+
+        let raw_files = parse_raw(&raw);
+        let mut truncated = raw_files.len() > MAX_FILES;
+        let mut files = Vec::new();
+        for mut file in raw_files.into_iter().take(MAX_FILES) {
+            // Type changes (like a file becoming a symlink) are listed
+            // once but patched as a deletion followed by an addition
+            let header = patch_header(&file);
+            let mut file_patches = Vec::new();
+            while let Some(patch) = patches.next_if(|patch| patch.lines().next() == Some(&header)) {
+                file_patches.push(patch);
+            }
+            if file_patches.is_empty() {
+                // Past the patches that were read, files can't be told
+                // apart from files without a patch
+                if patch_cut && patches.peek().is_none() {
+                    truncated = true;
+                    break;
+                }
+                files.push(file);
+                continue;
+            }
+
+            file.binary = file_patches
+                .iter()
+                .flat_map(|patch| patch.lines())
+                .any(|line| line.starts_with("Binary files ") || line == "GIT binary patch");
+            file.hunks = file_patches.into_iter().flat_map(parse_hunks).collect();
+            intraline::highlight(&mut file.hunks, query.intraline);
+            for line in file.hunks.iter().flat_map(|hunk| &hunk.lines) {
+                match line.kind {
+                    LineKind::Added => file.additions += 1,
+                    LineKind::Deleted => file.deletions += 1,
+                    LineKind::Context => {}
+                }
+            }
+            files.push(file);
+        }
+
+        // Patches of files that were left out are expected to be left over
+        match patches.next() {
+            Some(patch) if !truncated => Err(GitApiError::Backend(format!(
+                "Unmatched patch {}",
+                patch.lines().next().unwrap_or("")
+            ))),
+            _ => Ok((files, truncated)),
+        }
+    }
+}
+
+/// The first line of a file's patch, `diff --git a/<old path> b/<path>`.
+fn patch_header(file: &FileDiff) -> String {
+    let old_path = file.old_path.as_deref().unwrap_or(&file.path);
+
+    format!(
+        "diff --git {} {}",
+        quote_path(&format!("a/{old_path}")),
+        quote_path(&format!("b/{}", file.path))
+    )
+}
+
+/// Quote a path like git does when `core.quotePath` is off.
+fn quote_path(path: &str) -> String {
+    if !path
+        .chars()
+        .any(|c| c == '"' || c == '\\' || c.is_ascii_control())
+    {
+        return path.to_string();
+    }
+
+    let mut quoted = String::from('"');
+    for c in path.chars() {
+        match c {
+            '"' => quoted.push_str("\\\""),
+            '\\' => quoted.push_str("\\\\"),
+            '\u{7}' => quoted.push_str("\\a"),
+            '\u{8}' => quoted.push_str("\\b"),
+            '\t' => quoted.push_str("\\t"),
+            '\n' => quoted.push_str("\\n"),
+            '\u{b}' => quoted.push_str("\\v"),
+            '\u{c}' => quoted.push_str("\\f"),
+            '\r' => quoted.push_str("\\r"),
+            c if c.is_ascii_control() => quoted.push_str(&format!("\\{:03o}", c as u8)),
+            c => quoted.push(c),
+        }
+    }
+    quoted.push('"');
+
+    quoted
+}
+
+/// Try to resolve a revision to the full id of the commit it points at.
+async fn resolve_commit(
+    backend: &dyn GitBackend,
+    repo_path: &Path,
+    rev: &str,
+) -> Result<String, GitApiError> {
+    backend
+        .resolve_ref(repo_path, &format!("{rev}^{{commit}}"))
+        .await
+}
+
+/// Parse `git diff-tree -z --raw` output into files without hunks.
+///
+/// Every file looks like `:<old mode> <new mode> <old oid> <new oid> <status>\0<path>\0`,
+/// with the old path first for renames and copies.
+fn parse_raw(output: &str) -> Vec<FileDiff> {
+    let mut fields = output.split('\0').filter(|field| !field.is_empty());
+    let mut files = Vec::new();
+
+    while let Some(info) = fields.next() {
+        let Some(info) = info.strip_prefix(':') else {
+            continue;
+        };
+        let info: Vec<&str> = info.split(' ').collect();
+        let [old_mode, new_mode, _, _, status] = info.as_slice() else {
+            continue;
+        };
+        let Some(status) = ChangeStatus::parse(status) else {
+            continue;
+        };
+        let Some(mut path) = fields.next() else { break };
+
+        let mut old_path = None;
+        if matches!(status, ChangeStatus::Renamed | ChangeStatus::Copied) {
+            old_path = Some(path.to_string());
+            let Some(new_path) = fields.next() else { break };
+            path = new_path;
+        }
+
+        // Missing sides have an all zero mode
+        let mode = |mode: &str| (mode != "000000").then(|| mode.to_string());
+        files.push(FileDiff {
+            path: path.to_string(),
+            old_path,
+            status,
+            old_mode: mode(old_mode),
+            new_mode: mode(new_mode),
+            binary: false,
+            additions: 0,
+            deletions: 0,
+            hunks: Vec::new(),
+        });
+    }
+
+    files
+}
+
+/// Split `git diff-tree --patch` output into the patch of every file.
+fn split_patches(output: &str) -> Vec<&str> {
+    let mut starts: Vec<usize> = output
+        .match_indices("diff --git ")
+        .map(|(start, _)| start)
+        .filter(|&start| start == 0 || output.as_bytes()[start - 1] == b'\n')
+        .collect();
+    starts.push(output.len());
+
+    starts
+        .windows(2)
+        .map(|bounds| &output[bounds[0]..bounds[1]])
+        .collect()
+}
+
+/// Parse the hunks of a single file's patch.
+pub fn parse_hunks(patch: &str) -> Vec<Hunk> {
+    let mut hunks: Vec<Hunk> = Vec::new();
+    let (mut old_line, mut new_line) = (0, 0);
+
+    for line in patch.lines() {
+        if let Some(hunk) = parse_hunk_header(line) {
+            (old_line, new_line) = (hunk.old_start, hunk.new_start);
+            hunks.push(hunk);
+            continue;
+        }
+        // Everything before the first hunk is the file header
+        let Some(hunk) = hunks.last_mut() else {
+            continue;
+        };
+
+        let (kind, content) = match line.split_at_checked(1) {
+            Some((" ", content)) => (LineKind::Context, content),
+            Some(("+", content)) => (LineKind::Added, content),
+            Some(("-", content)) => (LineKind::Deleted, content),
+            // Like "\ No newline at end of file"
+            _ => continue,
+        };
+
+        let (old, new) = match kind {
+            LineKind::Context => (Some(old_line), Some(new_line)),
+            LineKind::Added => (None, Some(new_line)),
+            LineKind::Deleted => (Some(old_line), None),
+        };
+        old_line += usize::from(old.is_some());
+        new_line += usize::from(new.is_some());
+
+        hunk.lines.push(DiffLine {
+            kind,
+            old_line: old,
+            new_line: new,
+            content: content.to_string(),
+            changes: Vec::new(),
+        });
+    }
+
+    hunks
+}
+
+/// Parse a hunk header like `@@ -1,3 +1,4 @@ fn main() {`.
+fn parse_hunk_header(line: &str) -> Option<Hunk> {
+    let rest = line.strip_prefix("@@ -")?;
+    let (ranges, section) = rest.split_once(" @@")?;
+    let (old, new) = ranges.split_once(" +")?;
+
+    // The length is omitted when it's 1
+    let range = |range: &str| -> Option<(usize, usize)> {
+        match range.split_once(',') {
+            Some((start, lines)) => Some((start.parse().ok()?, lines.parse().ok()?)),
+            None => Some((range.parse().ok()?, 1)),
+        }
+    };
+    let (old_start, old_lines) = range(old)?;
+    let (new_start, new_lines) = range(new)?;
+
+    Some(Hunk {
+        old_start,
+        old_lines,
+        new_start,
+        new_lines,
+        section: section.trim().to_string(),
+        lines: Vec::new(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PATCH: &str = "diff --git a/src/main.rs b/src/main.rs
+index 1111111..2222222 100644
+--- a/src/main.rs
++++ b/src/main.rs
+@@ -1,3 +1,4 @@ fn main() {
+ one
+-two
++deux
++trois
+ four
+@@ -10 +11 @@
+-last
+\\ No newline at end of file
++last
+diff --git a/logo.png b/logo.png
+index 3333333..4444444 100644
+Binary files a/logo.png and b/logo.png differ
+";
+
+    #[test]
+    fn test_split_patches() {
+        let patches = split_patches(PATCH);
+
+        assert_eq!(patches.len(), 2);
+        assert!(patches[0].starts_with("diff --git a/src/main.rs"));
+        assert!(patches[1].starts_with("diff --git a/logo.png"));
+    }
+
+    #[test]
+    fn test_parse_hunks_numbers_lines() {
+        let hunks = parse_hunks(split_patches(PATCH)[0]);
+        assert_eq!(hunks.len(), 2);
+
+        let hunk = &hunks[0];
+        assert_eq!((hunk.old_start, hunk.old_lines), (1, 3));
+        assert_eq!((hunk.new_start, hunk.new_lines), (1, 4));
+        assert_eq!(hunk.section, "fn main() {");
+
+        let numbers: Vec<_> = hunk
+            .lines
+            .iter()
+            .map(|line| (line.kind, line.old_line, line.new_line))
+            .collect();
+        assert_eq!(
+            numbers,
+            [
+                (LineKind::Context, Some(1), Some(1)),
+                (LineKind::Deleted, Some(2), None),
+                (LineKind::Added, None, Some(2)),
+                (LineKind::Added, None, Some(3)),
+                (LineKind::Context, Some(3), Some(4)),
+            ]
+        );
+
+        // Single line ranges omit their length
+        let hunk = &hunks[1];
+        assert_eq!((hunk.old_start, hunk.old_lines), (10, 1));
+        assert_eq!(hunk.lines.len(), 2);
+        assert_eq!(hunk.lines[1].new_line, Some(11));
+    }
+
+    #[test]
+    fn test_parse_raw() {
+        let raw =
+            ":100644 100644 aaa bbb R087\0old name\0new name\0:000000 100755 000 ccc A\0run.sh\0";
+        let files = parse_raw(raw);
+
+        assert_eq!(files.len(), 2);
+        assert_eq!(files[0].path, "new name");
+        assert_eq!(files[0].old_path.as_deref(), Some("old name"));
+        assert_eq!(files[0].status, ChangeStatus::Renamed);
+        assert_eq!(files[1].old_mode, None);
+        assert_eq!(files[1].new_mode.as_deref(), Some("100755"));
+    }
+
+    #[test]
+    fn test_patch_headers_quote_like_git() {
+        let mut file = parse_raw(":100644 100644 aaa bbb R100\0old name\0tab\there\0").remove(0);
+        assert_eq!(
+            patch_header(&file),
+            "diff --git a/old name \"b/tab\\there\""
+        );
+
+        file.old_path = None;
+        file.path = "caf\u{e9}.txt".to_string();
+        assert_eq!(
+            patch_header(&file),
+            "diff --git a/caf\u{e9}.txt b/caf\u{e9}.txt"
+        );
+    }
+
+    #[test]
+    fn test_invalid_context() {
+        let query = DiffQuery {
+            context: Some(MAX_CONTEXT + 1),
+            ..DiffQuery::default()
+        };
+
+        assert!(matches!(query.args(), Err(GitApiError::InvalidInput(_))));
+    }
+}
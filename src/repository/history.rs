@@ -0,0 +1,195 @@
+use crate::{
+    application::GitApiError,
+    repository::{
+        backend::{cli::LOG_FORMAT, GitBackend},
+        commit::ChangeStatus,
+    },
+    utils::{
+        commands::run_git_command,
+        commits::Commit,
+        pagination::{page_bounds, OpenPageInfo},
+    },
+};
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+
+#[derive(Deserialize, Debug, Default)]
+#[serde(default, rename_all = "camelCase", deny_unknown_fields)]
+/// The query string of file histories, every field is optional
+pub struct HistoryQuery {
+    /// The page to get, starting at 1
+    pub page: Option<usize>,
+    pub per_page: Option<usize>,
+    /// Whether to count the lines every commit added to and deleted from the file
+    pub stats: bool,
+}
+
+#[derive(Serialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+/// A commit that changed a file
+pub struct FileCommit {
+    pub commit: Commit,
+    /// The path of the file after the commit
+    pub path: String,
+    /// The path of the file before the commit, when the commit renamed it
+    pub old_path: Option<String>,
+    pub status: ChangeStatus,
+    /// Lines added to the file, `None` unless asked for or when it's binary
+    pub additions: Option<usize>,
+    /// Lines deleted from the file, `None` unless asked for or when it's binary
+    pub deletions: Option<usize>,
+}
+
+#[derive(Serialize, Debug)]
+#[serde(rename_all = "camelCase")]
+/// A model representing a page of the commits that changed a file
+pub struct FileHistory {
+    pub path: String,
+    pub commits: Vec<FileCommit>,
+    /// Histories aren't counted as that would mean walking all of them
+    #[serde(flatten)]
+    pub page: OpenPageInfo,
+}
+impl FileHistory {
+    /// Try to get a page of the (non merge) commits that changed a file
+    /// up to a revision, the newest first, following it across renames.
+    pub async fn of_file(
+        backend: &dyn GitBackend,
+        repo_path: &Path,
+        rev: &str,
+        path: &str,
+        query: &HistoryQuery,
+    ) -> Result<FileHistory, GitApiError> {
+        let (page, per_page) = page_bounds(query.page, query.per_page)?;
+        let revision = backend
+            .resolve_ref(repo_path, &format!("{rev}^{{commit}}"))
+            .await?;
+
+        // Only walk as far as the page and one commit past it, skipping
+        // the pages before it here as `--skip` breaks `--follow`
+        let skip = (page - 1).saturating_mul(per_page);
+        let max_count = format!("--max-count={}", skip.saturating_add(per_page + 1));
+        // Paths are matched as is rather than as patterns
+        let mut args = vec![
+            "--literal-pathspecs",
+            "log",
+            "--follow",
+            "--no-merges",
+            "--date=iso-strict",
+            LOG_FORMAT,
+            "-z",
+            "--raw",
+            "--no-abbrev",
+            &max_count,
+        ];
+        if query.stats {
+            args.push("--numstat");
+        }
+        args.extend([revision.as_str(), "--", path]);
+        let output = run_git_command(repo_path, &args, false).await?;
+
+        let mut commits: Vec<FileCommit> = output
+            .split('\x1e')
+            .filter(|record| !record.trim().is_empty())
+            .filter_map(parse_record)
+            .skip(skip)
+            .collect();
+        let has_more = commits.len() > per_page;
+        commits.truncate(per_page);
+
+        Ok(FileHistory {
+            path: path.to_string(),
+            commits,
+            page: OpenPageInfo {
+                page,
+                per_page,
+                has_more,
+            },
+        })
+    }
+}
+
+/// Parse the record of a commit in `git log -z --raw [--numstat]` output.
+///
+/// The commit in [`LOG_FORMAT`] is followed by a newline and the raw
+/// change of the file (`:<modes> <oids> <status>\0<path>\0`, with the old
+/// path first for renames) and then its line counts, if asked for.
+fn parse_record(record: &str) -> Option<FileCommit> {
+    // Messages may have lines starting with ":" too, the raw change is the
+    // first one that looks like it
+    let start = record
+        .match_indices("\n:")
+        .map(|(i, _)| i + 1)
+        .find(|&i| raw_status(&record[i..]).is_some())?;
+    let (header, changes) = record.split_at(start);
+    let mut fields = changes.split('\0');
+
+    let status = raw_status(fields.next()?)?;
+    let mut path = fields.next()?;
+    let mut old_path = None;
+    if matches!(status, ChangeStatus::Renamed | ChangeStatus::Copied) {
+        old_path = Some(path.to_string());
+        path = fields.next()?;
+    }
+
+    // Like "<additions>\t<deletions>\t<path>", "-" for binary files
+    let mut counts = fields.next().unwrap_or("").split('\t');
+    let additions = counts.next().and_then(|count| count.parse().ok());
+    let deletions = counts.next().and_then(|count| count.parse().ok());
+
+    Some(FileCommit {
+        commit: Commit::from(header),
+        path: path.to_string(),
+        old_path,
+        status,
+        additions,
+        deletions,
+    })
+}
+
+/// Parse the status out of a raw change like
+/// `:100644 100644 <old oid> <new oid> R100`.
+fn raw_status(change: &str) -> Option<ChangeStatus> {
+    let change = change.strip_prefix(':')?;
+    let change = change.split('\0').next()?;
+    let parts: Vec<&str> = change.split(' ').collect();
+    let [old_mode, new_mode, _, _, status] = parts[..] else {
+        return None;
+    };
+    let is_mode = |mode: &str| mode.len() == 6 && mode.bytes().all(|b| b.is_ascii_digit());
+    if !is_mode(old_mode) || !is_mode(new_mode) {
+        return None;
+    }
+
+    ChangeStatus::parse(status)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_records() {
+        let commit = "fcc522f85c\x1fA\x1fa@b.c\x1f2024-01-02T03:04:05+00:00\x1f\x1ftwo\n";
+        let renamed = format!(
+            "{commit}\n:not a change\n\n:100644 100644 1191247 1191247 R100\0a.txt\0b.txt\x000\t0\t\0a.txt\0b.txt\0"
+        );
+        let record = parse_record(&renamed).unwrap();
+
+        assert_eq!(record.commit.msg, "two");
+        assert_eq!(record.path, "b.txt");
+        assert_eq!(record.old_path.as_deref(), Some("a.txt"));
+        assert_eq!(record.status, ChangeStatus::Renamed);
+        assert_eq!((record.additions, record.deletions), (Some(0), Some(0)));
+
+        let binary = format!("{commit}\n\n:100644 100644 aaa bbb M\0logo.png\0-\t-\tlogo.png\0");
+        let record = parse_record(&binary).unwrap();
+        assert_eq!(record.old_path, None);
+        assert_eq!(record.additions, None);
+
+        let without_stats = format!("{commit}\n\n:000000 100644 000 aaa A\0a.txt\0");
+        let record = parse_record(&without_stats).unwrap();
+        assert_eq!(record.status, ChangeStatus::Added);
+        assert_eq!(record.additions, None);
+    }
+}
@@ -0,0 +1,237 @@
+use crate::repository::diff::{Hunk, LineKind};
+use serde::{Deserialize, Serialize};
+
+/// Files whose added and deleted lines are larger than this (in bytes)
+/// aren't highlighted
+pub const MAX_FILE_BYTES: usize = 64 * 1024;
+/// Line pairs with more tokens than this (on either side) aren't highlighted
+pub const MAX_LINE_TOKENS: usize = 500;
+
+#[derive(Deserialize, Debug, Clone, Copy, Default, PartialEq)]
+#[serde(rename_all = "camelCase")]
+/// How changed lines are split up to find what changed within them
+pub enum Tokenizer {
+    /// Don't highlight changes within lines
+    Off,
+    /// Runs of letters, digits and underscores, runs of whitespace
+    /// and single punctuation characters
+    #[default]
+    Word,
+    Char,
+}
+impl Tokenizer {
+    /// Split a line into tokens, as `(start, end)` character offsets.
+    fn tokenize(self, line: &str) -> Vec<(usize, usize)> {
+        let chars: Vec<char> = line.chars().collect();
+        if self == Tokenizer::Char {
+            return (0..chars.len()).map(|i| (i, i + 1)).collect();
+        }
+
+        let class = |c: char| {
+            if c.is_alphanumeric() || c == '_' {
+                1
+            } else if c.is_whitespace() {
+                2
+            } else {
+                0
+            }
+        };
+
+        let mut tokens = Vec::new();
+        let mut start = 0;
+        for end in 1..=chars.len() {
+            let boundary = end == chars.len()
+                || class(chars[end]) != class(chars[start])
+                || class(chars[start]) == 0;
+            if boundary {
+                tokens.push((start, end));
+                start = end;
+            }
+        }
+
+        tokens
+    }
+}
+
+#[derive(Serialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "camelCase")]
+/// A changed part of a line, in characters from the start of the line
+pub struct Span {
+    pub start: usize,
+    /// Exclusive
+    pub end: usize,
+}
+
+/// Mark what changed within every deleted line that was replaced by an
+/// added line, pairing runs of deleted lines with the added lines right
+/// after them in order.
+pub fn highlight(hunks: &mut [Hunk], tokenizer: Tokenizer) {
+    if tokenizer == Tokenizer::Off {
+        return;
+    }
+
+    let changed_bytes: usize = hunks
+        .iter()
+        .flat_map(|hunk| &hunk.lines)
+        .filter(|line| line.kind != LineKind::Context)
+        .map(|line| line.content.len())
+        .sum();
+    if changed_bytes > MAX_FILE_BYTES {
+        return;
+    }
+
+    for hunk in hunks {
+        let lines = &mut hunk.lines;
+        let mut i = 0;
+        while i < lines.len() {
+            let deleted = lines[i..]
+                .iter()
+                .take_while(|line| line.kind == LineKind::Deleted)
+                .count();
+            let added = lines[i + deleted..]
+                .iter()
+                .take_while(|line| line.kind == LineKind::Added)
+                .count();
+            if deleted == 0 || added == 0 {
+                i += deleted.max(1);
+                continue;
+            }
+
+            for pair in 0..deleted.min(added) {
+                let (old, new) = lines.split_at_mut(i + deleted);
+                let (old, new) = (&mut old[i + pair], &mut new[pair]);
+                if let Some((old_spans, new_spans)) =
+                    changed_spans(&old.content, &new.content, tokenizer)
+                {
+                    old.changes = old_spans;
+                    new.changes = new_spans;
+                }
+            }
+            i += deleted + added;
+        }
+    }
+}
+
+/// Find the spans of `old` and `new` that aren't in their longest common
+/// subsequence of tokens.
+///
+/// Returns `None` when the lines are too long, or have nothing in
+/// common and are better shown as entirely replaced.
+fn changed_spans(old: &str, new: &str, tokenizer: Tokenizer) -> Option<(Vec<Span>, Vec<Span>)> {
+    let old_tokens = tokenizer.tokenize(old);
+    let new_tokens = tokenizer.tokenize(new);
+    if old_tokens.len() > MAX_LINE_TOKENS || new_tokens.len() > MAX_LINE_TOKENS {
+        return None;
+    }
+
+    let old_chars: Vec<char> = old.chars().collect();
+    let new_chars: Vec<char> = new.chars().collect();
+    let old_text = |i: usize| &old_chars[old_tokens[i].0..old_tokens[i].1];
+    let new_text = |j: usize| &new_chars[new_tokens[j].0..new_tokens[j].1];
+
+    // lengths[i][j] is the length of the LCS of old[i..] and new[j..]
+    let (n, m) = (old_tokens.len(), new_tokens.len());
+    let mut lengths = vec![vec![0u16; m + 1]; n + 1];
+    for i in (0..n).rev() {
+        for j in (0..m).rev() {
+            lengths[i][j] = if old_text(i) == new_text(j) {
+                lengths[i + 1][j + 1] + 1
+            } else {
+                lengths[i + 1][j].max(lengths[i][j + 1])
+            };
+        }
+    }
+    if lengths[0][0] == 0 {
+        return None;
+    }
+
+    let (mut old_changed, mut new_changed) = (vec![false; n], vec![false; m]);
+    let (mut i, mut j) = (0, 0);
+    while i < n || j < m {
+        if i < n && j < m && old_text(i) == new_text(j) {
+            (i, j) = (i + 1, j + 1);
+        } else if j == m || (i < n && lengths[i + 1][j] >= lengths[i][j + 1]) {
+            old_changed[i] = true;
+            i += 1;
+        } else {
+            new_changed[j] = true;
+            j += 1;
+        }
+    }
+
+    Some((
+        spans(&old_tokens, &old_changed),
+        spans(&new_tokens, &new_changed),
+    ))
+}
+
+/// Merge the changed tokens into spans, joining adjacent ones.
+fn spans(tokens: &[(usize, usize)], changed: &[bool]) -> Vec<Span> {
+    let mut spans: Vec<Span> = Vec::new();
+    for (&(start, end), _) in tokens.iter().zip(changed).filter(|(_, &changed)| changed) {
+        match spans.last_mut() {
+            Some(span) if span.end == start => span.end = end,
+            _ => spans.push(Span { start, end }),
+        }
+    }
+
+    spans
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn span(start: usize, end: usize) -> Span {
+        Span { start, end }
+    }
+
+    #[test]
+    fn test_word_tokens() {
+        let tokens = Tokenizer::Word.tokenize("let x_1 = f(é);");
+        let words: Vec<String> = tokens
+            .iter()
+            .map(|&(start, end)| {
+                "let x_1 = f(é);"
+                    .chars()
+                    .skip(start)
+                    .take(end - start)
+                    .collect()
+            })
+            .collect();
+
+        assert_eq!(
+            words,
+            ["let", " ", "x_1", " ", "=", " ", "f", "(", "é", ")", ";"]
+        );
+    }
+
+    #[test]
+    fn test_changed_words() {
+        let (old, new) = changed_spans(
+            "let total = a + b;",
+            "let sum = a + b + c;",
+            Tokenizer::Word,
+        )
+        .unwrap();
+
+        assert_eq!(old, [span(4, 9)]);
+        assert_eq!(new, [span(4, 7), span(15, 19)]);
+    }
+
+    #[test]
+    fn test_changed_chars() {
+        let (old, new) = changed_spans("colour", "color", Tokenizer::Char).unwrap();
+
+        assert_eq!(old, [span(4, 5)]);
+        assert!(new.is_empty());
+    }
+
+    #[test]
+    fn test_unrelated_or_huge_lines_are_skipped() {
+        assert_eq!(changed_spans("abc", "xyz", Tokenizer::Word), None);
+
+        let long = "x ".repeat(MAX_LINE_TOKENS);
+        assert_eq!(changed_spans(&long, "x", Tokenizer::Word), None);
+    }
+}
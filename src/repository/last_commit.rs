@@ -0,0 +1,96 @@
+use crate::{
+    application::GitApiError,
+    repository::{backend::GitBackend, tree::tree_at},
+    utils::{
+        cache::{revision_key, ByteCache, Weigh},
+        commits::Commit,
+    },
+};
+use serde::Serialize;
+use std::{collections::BTreeMap, path::Path, sync::Arc};
+
+#[derive(Serialize, Debug)]
+#[serde(rename_all = "camelCase")]
+/// A model representing the last commit that changed every entry of a
+/// directory, as seen from a revision
+pub struct LastCommits {
+    /// The commit the history was walked from
+    pub revision: String,
+    /// The id of the directory's tree
+    pub tree: String,
+    /// The path of the directory, empty for the root
+    pub path: String,
+    /// The last commit of every entry by name, entries no commit reachable
+    /// from the revision changed are left out
+    pub commits: BTreeMap<String, Commit>,
+}
+impl Weigh for LastCommits {
+    fn weigh(&self) -> usize {
+        self.revision.weigh()
+            + self.tree.weigh()
+            + self.path.weigh()
+            + self
+                .commits
+                .iter()
+                .map(|(name, commit)| name.weigh() + commit.weigh())
+                .sum::<usize>()
+    }
+}
+impl LastCommits {
+    /// Try to find the last commit of every entry of a tree living at a
+    /// path, walking the history from the revision (a full commit id)
+    /// once and only when it isn't cached yet.
+    pub async fn of_tree(
+        backend: &dyn GitBackend,
+        cache: &ByteCache<LastCommits>,
+        repo_path: &Path,
+        revision: &str,
+        tree: &str,
+        path: &str,
+    ) -> Result<Arc<LastCommits>, GitApiError> {
+        // Trees and commits never change, so neither do their last commits
+        // at a given path
+        let cache_key = revision_key(repo_path, tree, revision, path);
+        if let Some(cached) = cache.get(&cache_key) {
+            return Ok(cached);
+        }
+
+        let names: Vec<String> = backend
+            .read_tree(repo_path, tree)
+            .await?
+            .into_iter()
+            .map(|entry| entry.name)
+            .collect();
+        let directory = (!path.is_empty()).then_some(path);
+        let commits = backend
+            .last_commits(repo_path, revision, directory, &names)
+            .await?;
+
+        Ok(cache.insert(
+            cache_key,
+            LastCommits {
+                revision: revision.to_string(),
+                tree: tree.to_string(),
+                path: path.to_string(),
+                commits: commits.into_iter().collect(),
+            },
+        ))
+    }
+
+    /// Try to find the last commit of every entry of the directory at a
+    /// path in a revision.
+    pub async fn at_path(
+        backend: &dyn GitBackend,
+        cache: &ByteCache<LastCommits>,
+        repo_path: &Path,
+        rev: &str,
+        path: &str,
+    ) -> Result<Arc<LastCommits>, GitApiError> {
+        let revision = backend
+            .resolve_ref(repo_path, &format!("{rev}^{{commit}}"))
+            .await?;
+        let tree = tree_at(backend, repo_path, &revision, path).await?;
+
+        Self::of_tree(backend, cache, repo_path, &revision, &tree, path).await
+    }
+}
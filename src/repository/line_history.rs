@@ -0,0 +1,213 @@
+use crate::{
+    application::GitApiError,
+    repository::{
+        backend::{cli::LOG_FORMAT, GitBackend},
+        blame::blob_at,
+        diff::{parse_hunks, Hunk},
+    },
+    utils::{commands::run_git_command, commits::Commit},
+};
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+
+/// The most commits listed in a line history
+pub const LINE_HISTORY_COMMIT_LIMIT: usize = 100;
+/// The longest function name pattern accepted
+const MAX_FUNCTION_LENGTH: usize = 256;
+
+#[derive(Deserialize, Debug, Default)]
+#[serde(default, rename_all = "camelCase", deny_unknown_fields)]
+/// The query string of line histories, either a line range or a function
+pub struct LineHistoryQuery {
+    /// The first line to follow, starting at 1
+    pub start: Option<usize>,
+    /// The last line to follow (inclusive)
+    pub end: Option<usize>,
+    /// A regex matching the first line of the function to follow
+    pub function: Option<String>,
+}
+impl LineHistoryQuery {
+    /// Try to turn the query into a `git log -L` option for a file.
+    fn line_range(&self, path: &str) -> Result<String, GitApiError> {
+        match (self.start, self.end, &self.function) {
+            (None, None, None) => Err(GitApiError::InvalidInput(
+                "Either a line range or a function is needed".to_string(),
+            )),
+            (None, None, Some(function)) => {
+                // The pattern ends at the first colon
+                let valid = !function.is_empty()
+                    && function.len() <= MAX_FUNCTION_LENGTH
+                    && !function.contains(':')
+                    && !function.chars().any(char::is_control);
+                if !valid {
+                    return Err(GitApiError::InvalidInput(format!(
+                        "Invalid function pattern: {function}"
+                    )));
+                }
+                Ok(format!("-L:{function}:{path}"))
+            }
+            (_, _, Some(_)) => Err(GitApiError::InvalidInput(
+                "A line range and a function can't both be followed".to_string(),
+            )),
+            (Some(0), _, _) | (_, Some(0), _) => {
+                Err(GitApiError::InvalidInput("Lines start at 1".to_string()))
+            }
+            (Some(start), Some(end), _) if end < start => Err(GitApiError::InvalidInput(
+                "The last line is before the first".to_string(),
+            )),
+            (start, end, None) => Ok(format!(
+                "-L{},{}:{path}",
+                start.unwrap_or(1),
+                end.map(|end| end.to_string()).unwrap_or_default()
+            )),
+        }
+    }
+}
+
+#[derive(Serialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+/// A commit that changed the followed lines
+pub struct LineCommit {
+    pub commit: Commit,
+    /// The path of the file at the commit
+    pub path: String,
+    /// How the commit changed the lines, as they were at the commit
+    pub hunks: Vec<Hunk>,
+}
+
+#[derive(Serialize, Debug)]
+#[serde(rename_all = "camelCase")]
+/// A model representing how some lines of a file came to be
+pub struct LineHistory {
+    pub path: String,
+    /// The commit the lines were followed back from
+    pub revision: String,
+    /// The newest commits first, at most [`LINE_HISTORY_COMMIT_LIMIT`]
+    pub commits: Vec<LineCommit>,
+}
+impl LineHistory {
+    /// Try to get the (non merge) commits that changed some lines of a
+    /// file up to a revision, along with how they changed them.
+    pub async fn of_lines(
+        backend: &dyn GitBackend,
+        repo_path: &Path,
+        rev: &str,
+        path: &str,
+        query: &LineHistoryQuery,
+    ) -> Result<LineHistory, GitApiError> {
+        let line_range = query.line_range(path)?;
+        let revision = backend
+            .resolve_ref(repo_path, &format!("{rev}^{{commit}}"))
+            .await?;
+
+        // Make sure the file exists so a missing one isn't a failure
+        if blob_at(backend, repo_path, &revision, path)
+            .await?
+            .is_none()
+        {
+            return Err(GitApiError::ObjectNotFound(path.to_string()));
+        }
+
+        let limit = format!("-n{LINE_HISTORY_COMMIT_LIMIT}");
+        let args = [
+            "-c",
+            "core.quotePath=false",
+            "log",
+            "--no-merges",
+            "--date=iso-strict",
+            LOG_FORMAT,
+            &limit,
+            &line_range,
+            &revision,
+        ];
+
+        // Ranges past the end of the file and unmatched functions are the
+        // caller's mistake
+        let output = match run_git_command(repo_path, &args, false).await {
+            Ok(output) => output,
+            Err(GitApiError::CommandFailed(failure))
+                if failure.stderr.contains("has only") || failure.stderr.contains("-L ") =>
+            {
+                return Err(GitApiError::InvalidInput(failure.stderr));
+            }
+            Err(e) => return Err(e),
+        };
+
+        let commits = output
+            .split('\x1e')
+            .filter(|record| !record.trim().is_empty())
+            .filter_map(|record| parse_record(record, path))
+            .collect();
+
+        Ok(LineHistory {
+            path: path.to_string(),
+            revision,
+            commits,
+        })
+    }
+}
+
+/// Parse the record of a commit in `git log -L` output: the commit in
+/// [`LOG_FORMAT`] followed by the patch of the followed lines.
+fn parse_record(record: &str, path: &str) -> Option<LineCommit> {
+    // Patch lines are prefixed, so only the message can come before this
+    let start = record.rfind("\ndiff --git ")? + 1;
+    let (header, patch) = record.split_at(start);
+
+    // Files renamed since have their old path here, "/dev/null" is never
+    // the new side
+    let file_path = patch
+        .lines()
+        .find_map(|line| line.strip_prefix("+++ b/"))
+        .unwrap_or(path);
+
+    Some(LineCommit {
+        commit: Commit::from(header),
+        path: file_path.to_string(),
+        hunks: parse_hunks(patch),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_record() {
+        let record = "8a0fdb\x1fA\x1fa@b.c\x1f2024-01-02T03:04:05+00:00\x1f\x1fadd lib
+
+diff --git a/src/lib.rs b/src/lib.rs
+--- /dev/null
++++ b/src/lib.rs
+@@ -0,0 +1,2 @@
++fn main() {
++}
+";
+        let commit = parse_record(record, "lib.rs").unwrap();
+
+        assert_eq!(commit.commit.msg, "add lib");
+        assert_eq!(commit.path, "src/lib.rs");
+        assert_eq!(commit.hunks.len(), 1);
+        assert_eq!(commit.hunks[0].lines[1].new_line, Some(2));
+    }
+
+    #[test]
+    fn test_line_ranges() {
+        let range = |start, end, function: Option<&str>| {
+            LineHistoryQuery {
+                start,
+                end,
+                function: function.map(str::to_string),
+            }
+            .line_range("a.rs")
+        };
+
+        assert_eq!(range(Some(2), Some(4), None).unwrap(), "-L2,4:a.rs");
+        assert_eq!(range(None, Some(4), None).unwrap(), "-L1,4:a.rs");
+        assert_eq!(range(None, None, Some("main")).unwrap(), "-L:main:a.rs");
+        assert!(range(None, None, None).is_err());
+        assert!(range(Some(1), None, Some("main")).is_err());
+        assert!(range(None, None, Some("a:b")).is_err());
+        assert!(range(Some(4), Some(2), None).is_err());
+    }
+}
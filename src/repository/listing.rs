@@ -0,0 +1,243 @@
+use crate::{
+    application::GitApiError,
+    repository::repo::RepoData,
+    utils::pagination::{paginate, PageInfo},
+};
+use chrono::{DateTime, NaiveDate};
+use serde::{Deserialize, Serialize};
+use std::cmp::Ordering;
+
+#[derive(Deserialize, Debug, Clone, Copy, Default, PartialEq)]
+#[serde(rename_all = "camelCase")]
+/// What the repository list is sorted by
+pub enum RepoSort {
+    Name,
+    #[default]
+    LastCommit,
+    /// The date of the first commit
+    Created,
+}
+
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum SortOrder {
+    Asc,
+    Desc,
+}
+
+#[derive(Deserialize, Debug, Default)]
+#[serde(default, rename_all = "camelCase", deny_unknown_fields)]
+/// The query string of the repository list, every field is optional
+pub struct RepoListQuery {
+    /// The page to get, starting at 1
+    pub page: Option<usize>,
+    pub per_page: Option<usize>,
+    pub sort: RepoSort,
+    /// Defaults to ascending for names and descending for dates
+    pub order: Option<SortOrder>,
+    /// Only keep repositories whose name contains this (case insensitive)
+    pub name: Option<String>,
+    /// Only keep repositories whose description contains this (case insensitive)
+    pub description: Option<String>,
+    /// Only keep repositories with a commit since this date (`YYYY-MM-DD` or RFC 3339)
+    pub active_since: Option<String>,
+    /// Only keep repositories with their last commit before this date (`YYYY-MM-DD` or RFC 3339)
+    pub active_until: Option<String>,
+}
+impl RepoListQuery {
+    /// Try to filter, sort and paginate a list of repositories.
+    pub fn apply(&self, mut repos: Vec<RepoData>) -> Result<RepoPage, GitApiError> {
+        let since = self.active_since.as_deref().map(parse_bound).transpose()?;
+        let until = self.active_until.as_deref().map(parse_bound).transpose()?;
+        let name = self.name.as_deref().map(str::to_lowercase);
+        let description = self.description.as_deref().map(str::to_lowercase);
+
+        repos.retain(|repo| {
+            let last_activity = repo.last_commit.as_ref().map(|commit| commit.timestamp);
+
+            name.as_ref()
+                .is_none_or(|name| repo.name.to_lowercase().contains(name))
+                && description
+                    .as_ref()
+                    .is_none_or(|description| repo.description.to_lowercase().contains(description))
+                && since.is_none_or(|since| last_activity.is_some_and(|t| t >= since))
+                && until.is_none_or(|until| last_activity.is_some_and(|t| t < until))
+        });
+
+        self.sort(&mut repos);
+
+        let (repos, page) = paginate(repos, self.page, self.per_page)?;
+
+        Ok(RepoPage { repos, page })
+    }
+
+    /// Sort repositories, the ones without the sort key (no commits) last.
+    fn sort(&self, repos: &mut [RepoData]) {
+        let order = self.order.unwrap_or(match self.sort {
+            RepoSort::Name => SortOrder::Asc,
+            RepoSort::LastCommit | RepoSort::Created => SortOrder::Desc,
+        });
+        let key = |repo: &RepoData| match self.sort {
+            RepoSort::Name => Some(0),
+            RepoSort::LastCommit => repo.last_commit.as_ref().map(|c| c.timestamp),
+            RepoSort::Created => repo.first_commit.as_ref().map(|c| c.timestamp),
+        };
+
+        repos.sort_by(|a, b| {
+            let ordering = match (key(a), key(b)) {
+                (Some(_), None) => return Ordering::Less,
+                (None, Some(_)) => return Ordering::Greater,
+                (a_key, b_key) => a_key
+                    .cmp(&b_key)
+                    .then_with(|| a.name.to_lowercase().cmp(&b.name.to_lowercase())),
+            };
+
+            match order {
+                SortOrder::Asc => ordering,
+                SortOrder::Desc => ordering.reverse(),
+            }
+        });
+    }
+}
+
+/// Parse a date (at midnight UTC) or datetime into a unix timestamp.
+fn parse_bound(date: &str) -> Result<i64, GitApiError> {
+    if let Ok(datetime) = DateTime::parse_from_rfc3339(date) {
+        return Ok(datetime.timestamp());
+    }
+
+    NaiveDate::parse_from_str(date, "%Y-%m-%d")
+        .ok()
+        .and_then(|date| date.and_hms_opt(0, 0, 0))
+        .map(|datetime| datetime.and_utc().timestamp())
+        .ok_or_else(|| GitApiError::InvalidInput(format!("Invalid date {date}")))
+}
+
+#[derive(Serialize, Debug)]
+#[serde(rename_all = "camelCase")]
+/// A model representing a page of the repository list
+pub struct RepoPage {
+    pub repos: Vec<RepoData>,
+    /// Where the page is in the repositories matching the filters
+    #[serde(flatten)]
+    pub page: PageInfo,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{repository::repo::RepoStatus, utils::commits::Commit};
+    use actix_web::web::Query;
+
+    fn repo(name: &str, description: &str, created: &str, last: Option<&str>) -> RepoData {
+        let commit = |date: &str| Commit::new("abcdef", "A", "a@b.c", date, "msg");
+
+        RepoData {
+            name: name.to_string(),
+            description: description.to_string(),
+            status: if last.is_some() {
+                RepoStatus::Ok
+            } else {
+                RepoStatus::Empty
+            },
+            default_branch: Some("master".to_string()),
+            first_commit: last.map(|_| commit(created)),
+            last_commit: last.map(commit),
+            error: None,
+        }
+    }
+
+    fn repos() -> Vec<RepoData> {
+        vec![
+            repo(
+                "alpha",
+                "A parser",
+                "2020-01-01T00:00:00+00:00",
+                Some("2024-03-01T00:00:00+00:00"),
+            ),
+            repo(
+                "beta",
+                "A web server",
+                "2022-01-01T00:00:00+00:00",
+                Some("2024-01-01T00:00:00+00:00"),
+            ),
+            repo("gamma", "Dotfiles", "", None),
+            repo(
+                "Delta",
+                "A Parser too",
+                "2021-01-01T00:00:00+00:00",
+                Some("2023-06-01T12:00:00+02:00"),
+            ),
+        ]
+    }
+
+    fn names(page: &RepoPage) -> Vec<&str> {
+        page.repos.iter().map(|r| r.name.as_str()).collect()
+    }
+
+    fn query(query: &str) -> RepoListQuery {
+        Query::<RepoListQuery>::from_query(query)
+            .unwrap()
+            .into_inner()
+    }
+
+    #[test]
+    fn test_default_is_most_recently_active_first() {
+        let page = query("").apply(repos()).unwrap();
+
+        assert_eq!(names(&page), ["alpha", "beta", "Delta", "gamma"]);
+        assert_eq!(page.page.total, 4);
+    }
+
+    #[test]
+    fn test_sorting() {
+        let page = query("sort=name").apply(repos()).unwrap();
+        assert_eq!(names(&page), ["alpha", "beta", "Delta", "gamma"]);
+
+        let page = query("sort=created&order=asc").apply(repos()).unwrap();
+        assert_eq!(names(&page), ["alpha", "Delta", "beta", "gamma"]);
+
+        let page = query("sort=lastCommit&order=asc").apply(repos()).unwrap();
+        assert_eq!(names(&page), ["Delta", "beta", "alpha", "gamma"]);
+    }
+
+    #[test]
+    fn test_filtering() {
+        let page = query("description=parser").apply(repos()).unwrap();
+        assert_eq!(names(&page), ["alpha", "Delta"]);
+
+        let page = query("name=ta").apply(repos()).unwrap();
+        assert_eq!(names(&page), ["beta", "Delta"]);
+
+        let page = query("activeSince=2023-12-31&activeUntil=2024-02-01T00:00:00Z")
+            .apply(repos())
+            .unwrap();
+        assert_eq!(names(&page), ["beta"]);
+        assert_eq!(page.page.total, 1);
+    }
+
+    #[test]
+    fn test_pagination_counts_the_filtered_repositories() {
+        let page = query("description=parser&perPage=1&page=2")
+            .apply(repos())
+            .unwrap();
+        assert_eq!(names(&page), ["Delta"]);
+        assert_eq!(page.page.total, 2);
+    }
+
+    #[test]
+    fn test_invalid_queries() {
+        for invalid in [
+            "page=0",
+            "perPage=0",
+            "perPage=1000",
+            "activeSince=yesterday",
+        ] {
+            let result = query(invalid).apply(repos());
+            assert!(
+                matches!(result, Err(GitApiError::InvalidInput(_))),
+                "{invalid}"
+            );
+        }
+    }
+}
@@ -0,0 +1,195 @@
+use crate::{
+    application::GitApiError,
+    repository::{backend::GitBackend, compare::Comparison},
+    utils::commands::run_git_command_with_status,
+};
+use serde::Serialize;
+use std::path::Path;
+
+#[derive(Serialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub enum MergeStatus {
+    Clean,
+    Conflicted,
+}
+
+#[derive(Serialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+/// A path that would be left conflicted by a merge
+pub struct Conflict {
+    pub path: String,
+    /// What kind of conflict it is, like `contents` or `modify/delete`
+    pub kind: String,
+    /// How git describes the conflict
+    pub message: String,
+}
+
+#[derive(Serialize, Debug)]
+#[serde(rename_all = "camelCase")]
+/// A model representing what merging head into base would do
+pub struct MergePreview {
+    /// The commit the base branch points at
+    pub base: String,
+    /// The commit the head branch points at
+    pub head: String,
+    pub merge_base: String,
+    pub status: MergeStatus,
+    /// The tree the merge would make, only when it's clean
+    pub tree: Option<String>,
+    pub conflicts: Vec<Conflict>,
+}
+impl MergePreview {
+    /// Try to merge head into base in memory, without touching any refs.
+    pub async fn between(
+        backend: &dyn GitBackend,
+        repo_path: &Path,
+        base: &str,
+        head: &str,
+    ) -> Result<MergePreview, GitApiError> {
+        let base = backend
+            .resolve_ref(repo_path, &format!("refs/heads/{base}^{{commit}}"))
+            .await?;
+        let head = backend
+            .resolve_ref(repo_path, &format!("refs/heads/{head}^{{commit}}"))
+            .await?;
+        let merge_base = Comparison::merge_base(repo_path, &base, &head)
+            .await?
+            .ok_or_else(|| {
+                GitApiError::InvalidInput("The branches have no history in common".to_string())
+            })?;
+
+        // Conflicts make it exit with 1, anything else is a failure
+        let args = [
+            "merge-tree",
+            "--write-tree",
+            "-z",
+            "--name-only",
+            &base,
+            &head,
+        ];
+        let (status, output) = run_git_command_with_status(repo_path, &args).await?;
+        let status = match status {
+            Some(0) => MergeStatus::Clean,
+            Some(1) => MergeStatus::Conflicted,
+            status => {
+                return Err(GitApiError::Backend(format!(
+                    "git merge-tree exited with {status:?}"
+                )))
+            }
+        };
+
+        let (tree, conflicts) = parse_merge_tree(&output)
+            .ok_or_else(|| GitApiError::Backend("Bad git merge-tree output".to_string()))?;
+
+        Ok(MergePreview {
+            base,
+            head,
+            merge_base,
+            tree: (status == MergeStatus::Clean).then_some(tree),
+            status,
+            conflicts,
+        })
+    }
+}
+
+/// Parse `git merge-tree --write-tree -z --name-only` output into the
+/// tree id and the conflicts.
+///
+/// The output is the tree id, then (only when there are conflicts) every
+/// conflicted path, an empty field and the messages, each looking like
+/// `<number of paths>\0<paths...>\0<type>\0<message>\0`.
+fn parse_merge_tree(output: &str) -> Option<(String, Vec<Conflict>)> {
+    let mut fields = output.split('\0');
+    let tree = fields.next()?.trim().to_string();
+
+    let mut paths = Vec::new();
+    for path in fields.by_ref() {
+        if path.is_empty() {
+            break;
+        }
+        paths.push(path);
+    }
+
+    let mut conflicts = Vec::new();
+    while let Some(count) = fields.next().and_then(|count| count.parse::<usize>().ok()) {
+        let message_paths: Vec<&str> = fields.by_ref().take(count).collect();
+        let (Some(kind), Some(message)) = (fields.next(), fields.next()) else {
+            break;
+        };
+        let Some(kind) = kind
+            .strip_prefix("CONFLICT (")
+            .and_then(|kind| kind.strip_suffix(')'))
+        else {
+            continue;
+        };
+
+        // A path is reported by the first conflict mentioning it
+        for path in message_paths {
+            let reported = conflicts.iter().any(|c: &Conflict| c.path == path);
+            if paths.contains(&path) && !reported {
+                conflicts.push(Conflict {
+                    path: path.to_string(),
+                    kind: kind.to_string(),
+                    message: message.trim().to_string(),
+                });
+            }
+        }
+    }
+
+    Some((tree, conflicts))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_merge_tree() {
+        let output = [
+            "e297f1",
+            "a",
+            "b",
+            "",
+            "1",
+            "a",
+            "Auto-merging",
+            "Auto-merging a\n",
+            "1",
+            "a",
+            "CONFLICT (contents)",
+            "CONFLICT (content): Merge conflict in a\n",
+            "1",
+            "b",
+            "CONFLICT (modify/delete)",
+            "CONFLICT (modify/delete): b deleted in x\n",
+            "",
+        ]
+        .join("\0");
+        let (tree, conflicts) = parse_merge_tree(&output).unwrap();
+
+        assert_eq!(tree, "e297f1");
+        assert_eq!(
+            conflicts,
+            [
+                Conflict {
+                    path: "a".to_string(),
+                    kind: "contents".to_string(),
+                    message: "CONFLICT (content): Merge conflict in a".to_string(),
+                },
+                Conflict {
+                    path: "b".to_string(),
+                    kind: "modify/delete".to_string(),
+                    message: "CONFLICT (modify/delete): b deleted in x".to_string(),
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_parse_clean_merge_tree() {
+        let (tree, conflicts) = parse_merge_tree("286ef4\0").unwrap();
+
+        assert_eq!(tree, "286ef4");
+        assert!(conflicts.is_empty());
+    }
+}
@@ -1,2 +1,18 @@
+pub mod backend;
+pub mod blame;
+pub mod branch;
+pub mod commit;
+pub mod compare;
+pub mod diff;
+pub mod history;
+pub mod intraline;
+pub mod last_commit;
+pub mod line_history;
+pub mod listing;
+pub mod merge;
 pub mod object;
+pub mod path_index;
 pub mod repo;
+pub mod repo_list;
+pub mod tag;
+pub mod tree;
@@ -1,11 +1,11 @@
 use crate::{
-    application::{GitApiError, REPOS_PATH},
-    utils::{
-        commands::{change_directory, get_filename_from_hash, run_git_command},
-        validation::validate_repo_path,
-    },
+    application::GitApiError,
+    repository::{backend::GitBackend, path_index::PathIndex},
+    utils::cache::Weigh,
 };
+use base64::{engine::general_purpose, Engine as _};
 use serde::Serialize;
+use std::path::Path;
 
 #[derive(Serialize, Debug)]
 /// Model representing an object in a repository
@@ -18,16 +18,24 @@ pub struct Object {
 }
 impl Object {
     /// Try to get a specific objects content in a repo by a given hash
-    pub async fn by_hash(repo: &str, hash: &str) -> Result<Object, GitApiError> {
-        // Validate and construct safe repository path
-        let repo_path = validate_repo_path(REPOS_PATH, repo)?;
-        let repo_path_str = repo_path.to_str().ok_or(GitApiError::InvalidInput(
-            "Repository path is of invalid encoding".into(),
-        ))?;
-        change_directory(repo_path_str)?;
+    pub async fn by_hash(
+        backend: &dyn GitBackend,
+        path_index: &PathIndex,
+        repo_path: &Path,
+        hash: &str,
+    ) -> Result<Object, GitApiError> {
+        let name = path_index.filename(backend, repo_path, hash).await?;
+        Self::from_blob(backend, repo_path, hash, &name).await
+    }
 
-        // Parse out the filename and extension
-        let name = get_filename_from_hash(hash)?;
+    /// Try to read a blob's content, `name` being the path it lives at
+    pub async fn from_blob(
+        backend: &dyn GitBackend,
+        repo_path: &Path,
+        hash: &str,
+        name: &str,
+    ) -> Result<Object, GitApiError> {
+        // Parse out the extension
         let mut ext = name
             .split('.')
             .collect::<Vec<&str>>()
@@ -45,20 +53,67 @@ impl Object {
         // it's an image extension than convert the content
         // to bytes and then base64 encode the bytes.
         let image_exts = ["png", "jpg", "jpeg"];
+        let blob = backend.read_blob(repo_path, hash).await?;
+        let size = blob.len().to_string();
         let content = if image_exts.contains(&ext.as_str()) {
-            run_git_command(&["show", "-p", hash], true)?
+            general_purpose::STANDARD_NO_PAD.encode(blob)
         } else {
-            run_git_command(&["show", "-p", hash], false)?
+            String::from_utf8(blob)?
         };
 
-        // Collect the objects data
-        let size = run_git_command(&["cat-file", "-s", hash], false)?;
-
         Ok(Object {
-            name,
+            name: name.to_string(),
             content,
             size,
             ext,
         })
     }
 }
+impl Weigh for Object {
+    fn weigh(&self) -> usize {
+        self.name.weigh() + self.content.weigh() + self.size.weigh() + self.ext.weigh()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::repository::backend::fake::FakeBackend;
+
+    #[tokio::test]
+    async fn test_by_hash_reads_text_files() {
+        let backend = FakeBackend::default().blob("b1", "src/main.rs", b"fn main() {}");
+
+        let object = Object::by_hash(&backend, &PathIndex::default(), Path::new("repo"), "b1")
+            .await
+            .unwrap();
+
+        assert_eq!(object.name, "src/main.rs");
+        assert_eq!(object.ext, "rs");
+        assert_eq!(object.size, "12");
+        assert_eq!(object.content, "fn main() {}");
+    }
+
+    #[tokio::test]
+    async fn test_by_hash_encodes_images() {
+        let backend = FakeBackend::default().blob("b1", "logo.png", &[0x89, 0x50, 0x4e]);
+
+        let object = Object::by_hash(&backend, &PathIndex::default(), Path::new("repo"), "b1")
+            .await
+            .unwrap();
+
+        assert_eq!(object.ext, "png");
+        assert_eq!(object.content, "iVBO");
+    }
+
+    #[tokio::test]
+    async fn test_files_without_extension_are_diffs() {
+        let backend = FakeBackend::default().blob("b1", "LICENSE", b"MIT");
+
+        let object = Object::by_hash(&backend, &PathIndex::default(), Path::new("repo"), "b1")
+            .await
+            .unwrap();
+
+        assert_eq!(object.ext, "diff");
+    }
+}
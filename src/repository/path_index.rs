@@ -0,0 +1,153 @@
+use crate::{
+    application::GitApiError,
+    repository::backend::{GitBackend, GitRef},
+};
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex},
+};
+
+/// The index of a single repository, along with the refs it was built
+/// from so it can tell when it's gone stale.
+struct RepoPaths {
+    refs: Vec<GitRef>,
+    /// Every path each object lives at, commits have none.
+    paths: HashMap<String, Vec<String>>,
+}
+
+/// The index of a repository, locked while it's (re)built so concurrent
+/// requests for the same repository wait for a single build.
+type Slot = Arc<tokio::sync::Mutex<Option<Arc<RepoPaths>>>>;
+
+/// A lazily built, per repository index of object id -> paths.
+///
+/// An index is only built the first time a repository is looked up in,
+/// and rebuilt whenever its refs have changed since.
+#[derive(Default)]
+pub struct PathIndex {
+    repos: Mutex<HashMap<PathBuf, Slot>>,
+}
+impl PathIndex {
+    /// Get every path an object lives at in the history of a repository.
+    ///
+    /// The object id has to be complete, commits live at no path.
+    pub async fn paths(
+        &self,
+        backend: &dyn GitBackend,
+        repo_path: &Path,
+        oid: &str,
+    ) -> Result<Vec<String>, GitApiError> {
+        self.index(backend, repo_path)
+            .await?
+            .paths
+            .get(oid)
+            .cloned()
+            .ok_or(GitApiError::ObjectNotFound(oid.to_string()))
+    }
+
+    /// Try to derive a filename from a (possibly abbreviated) hash.
+    ///
+    /// Objects living at more than one path are named after the first
+    /// one, commits are named after their hash.
+    pub async fn filename(
+        &self,
+        backend: &dyn GitBackend,
+        repo_path: &Path,
+        hash: &str,
+    ) -> Result<String, GitApiError> {
+        let oid = backend.resolve_ref(repo_path, hash).await?;
+        let paths = self.paths(backend, repo_path, &oid).await?;
+
+        Ok(paths.into_iter().next().unwrap_or(oid))
+    }
+
+    /// Get the index of a repository, building it if it's missing or stale.
+    async fn index(
+        &self,
+        backend: &dyn GitBackend,
+        repo_path: &Path,
+    ) -> Result<Arc<RepoPaths>, GitApiError> {
+        let slot = self
+            .repos
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .entry(repo_path.to_path_buf())
+            .or_default()
+            .clone();
+        let mut slot = slot.lock().await;
+
+        let refs = backend.refs(repo_path).await?;
+        if let Some(index) = slot.as_ref().filter(|index| index.refs == refs) {
+            return Ok(index.clone());
+        }
+
+        let mut paths: HashMap<String, Vec<String>> = HashMap::new();
+        for object in backend.object_paths(repo_path).await? {
+            let object_paths = paths.entry(object.oid).or_default();
+            if let Some(path) = object.path {
+                object_paths.push(path);
+            }
+        }
+        for object_paths in paths.values_mut() {
+            object_paths.sort();
+            object_paths.dedup();
+        }
+
+        let index = Arc::new(RepoPaths { refs, paths });
+        *slot = Some(index.clone());
+
+        Ok(index)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{repository::backend::cli::CliBackend, test_utils::Fixture};
+
+    #[tokio::test]
+    async fn test_paths_are_exact_and_complete() {
+        let fixture = Fixture::new();
+        let repo = fixture.repo("alpha");
+        repo.write("a.txt", "same\n")
+            .write("dir with spaces/b c.txt", "same\n")
+            .write("other.txt", "other\n");
+        let commit = repo.commit("Initial commit");
+        let blob = repo.git(&["rev-parse", "HEAD:a.txt"]);
+
+        let index = PathIndex::default();
+        let paths = index.paths(&CliBackend, &repo.bare, &blob).await.unwrap();
+        assert_eq!(paths, vec!["a.txt", "dir with spaces/b c.txt"]);
+
+        // Abbreviations and substrings aren't object ids
+        let result = index.paths(&CliBackend, &repo.bare, &blob[..7]).await;
+        assert!(matches!(result, Err(GitApiError::ObjectNotFound(_))));
+        let result = index.paths(&CliBackend, &repo.bare, &blob[1..]).await;
+        assert!(matches!(result, Err(GitApiError::ObjectNotFound(_))));
+
+        // ... but filenames can still be derived from abbreviations
+        let name = index.filename(&CliBackend, &repo.bare, &blob[..7]);
+        assert_eq!(name.await.unwrap(), "a.txt");
+        let name = index.filename(&CliBackend, &repo.bare, &commit[..7]);
+        assert_eq!(name.await.unwrap(), commit);
+    }
+
+    #[tokio::test]
+    async fn test_index_is_refreshed_when_refs_change() {
+        let fixture = Fixture::new();
+        let repo = fixture.repo("alpha");
+        repo.write("a.txt", "a\n");
+        repo.commit("Initial commit");
+
+        let index = PathIndex::default();
+        let blob = repo.git(&["rev-parse", "HEAD:a.txt"]);
+        assert!(index.paths(&CliBackend, &repo.bare, &blob).await.is_ok());
+
+        repo.write("b.txt", "b\n");
+        repo.commit("Add b");
+        let blob = repo.git(&["rev-parse", "HEAD:b.txt"]);
+        let paths = index.paths(&CliBackend, &repo.bare, &blob).await.unwrap();
+        assert_eq!(paths, vec!["b.txt"]);
+    }
+}
@@ -1,18 +1,57 @@
 use crate::{
-    application::{GitApiError, REPOS_PATH},
+    application::GitApiError,
+    repository::{
+        backend::{GitBackend, TreeEntry},
+        last_commit::LastCommits,
+        path_index::PathIndex,
+        repo_list::RepoListCache,
+    },
     utils::{
-        commands::{change_directory, get_filename_from_hash, run_git_command},
+        cache::{ByteCache, Weigh},
         commits::Commit,
-        dates::parse_string_to_date,
-        validation::validate_repo_path,
     },
 };
-use serde::Serialize;
+use serde::{Deserialize, Serialize};
 use std::{
-    fs::{self, ReadDir},
+    collections::HashSet,
+    fs,
+    path::{Path, PathBuf},
     result::Result,
 };
 
+#[derive(Deserialize, Debug)]
+#[serde(default, rename_all = "camelCase", deny_unknown_fields)]
+/// The query string of repositories by branch, every field is optional
+pub struct BranchQuery {
+    /// Whether to find the last commit of every entry, `false` to list the
+    /// entries right away and get their last commits from `/last-commits`
+    pub last_commits: bool,
+}
+impl Default for BranchQuery {
+    fn default() -> Self {
+        BranchQuery { last_commits: true }
+    }
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(default, rename_all = "camelCase", deny_unknown_fields)]
+/// The query string of repositories by hash, every field is optional
+pub struct HashQuery {
+    /// The revision to find the last commits from, `HEAD` when omitted
+    pub rev: Option<String>,
+    /// Whether to find the last commit of every entry, `false` to list the
+    /// entries right away and get their last commits from `/last-commits`
+    pub last_commits: bool,
+}
+impl Default for HashQuery {
+    fn default() -> Self {
+        HashQuery {
+            rev: None,
+            last_commits: true,
+        }
+    }
+}
+
 #[derive(Serialize, Debug)]
 #[serde(rename_all = "camelCase")]
 /// A model representing a repository
@@ -20,57 +59,73 @@ pub struct Repo {
     pub objects: Vec<RepoBranchFile>,
     pub read_me: Option<String>,
 }
+impl Weigh for Repo {
+    fn weigh(&self) -> usize {
+        self.objects.weigh() + self.read_me.weigh()
+    }
+}
 impl Repo {
     /// Get all repositories on the server.
-    pub async fn get_all() -> Result<Vec<RepoData>, GitApiError> {
-        // Start at the file path holding all the repositories
-        change_directory(REPOS_PATH)?;
-
-        // Read all the files in the directory and map them into
-        // repository data (`RepoData`)
-        fs::read_dir(REPOS_PATH).map(|files_in_dir| -> Result<Vec<RepoData>, GitApiError> {
-            let mut repos_in_dir = Self::into_repos_in_dir(files_in_dir)?;
-
-            // Sort the repositories by date and reverse the order
-            // (most recent, ..., oldest)
-            repos_in_dir.sort_by_key(|a| parse_string_to_date(&a.last_commit.date));
-            repos_in_dir.reverse();
-
-            Ok(repos_in_dir)
-        })?
+    ///
+    /// Repositories in earlier roots shadow repositories
+    /// with the same name in later roots.
+    pub async fn get_all(
+        backend: &dyn GitBackend,
+        repos_cache: &RepoListCache,
+        roots: &[PathBuf],
+    ) -> Result<Vec<RepoData>, GitApiError> {
+        let mut repos: Vec<RepoData> = Vec::new();
+
+        // Map every repository into repository data (`RepoData`),
+        // reusing the cached data of repositories that didn't change and
+        // dropping the data of the ones that are gone
+        let repo_paths = Self::find_all(roots);
+        repos_cache.retain(&repo_paths);
+        for repo_path in repo_paths {
+            let load = Self::load_data(backend, &repo_path);
+            repos.push(repos_cache.get_or_load(&repo_path, load).await);
+        }
+
+        Ok(repos)
     }
 
     /// Get a repo at a specified state using a given repo name and hash
     ///
     /// Called when a user clicks a directory in a repo branch, so it's meant
     /// to basically treat directories in a repository as sub repositories.
-    pub async fn by_hash(repo: &str, hash: &str) -> Result<Repo, GitApiError> {
-        // Validate and construct safe repository path
-        let repo_path = validate_repo_path(REPOS_PATH, repo)?;
-        let repo_path_str = repo_path
-            .to_str()
-            .ok_or(GitApiError::InvalidInput("Invalid path encoding".into()))?;
-        change_directory(repo_path_str)?;
-
-        let parent_path = get_filename_from_hash(hash)?;
-
-        // Grab all the objects in the repo using the "git ls-tree {HASH}" command
-        // and trying to parse out the command output into repo objects
-        let objects_in_repo = run_git_command(&["ls-tree", hash], false)?
-            .lines()
-            .filter_map(
-                |object| match Self::parse_object(object, Some(&parent_path)) {
-                    Ok(object) => Some(object),
-                    Err(e) => {
-                        eprintln!("{e:?}");
-                        None
-                    }
-                },
+    /// The last commits are found from `revision` (a full commit id), or
+    /// left out when they're fetched separately.
+    pub async fn by_hash(
+        backend: &dyn GitBackend,
+        path_index: &PathIndex,
+        last_commit_cache: &ByteCache<LastCommits>,
+        repo_path: &Path,
+        hash: &str,
+        revision: &str,
+        with_last_commits: bool,
+    ) -> Result<Repo, GitApiError> {
+        let parent_path = path_index.filename(backend, repo_path, hash).await?;
+
+        // Grab all the objects in the tree with the given hash
+        // and try to turn them into repo objects
+        let entries = backend.read_tree(repo_path, hash).await?;
+        let last_commits = if with_last_commits {
+            let last_commits = LastCommits::of_tree(
+                backend,
+                last_commit_cache,
+                repo_path,
+                revision,
+                hash,
+                &parent_path,
             )
-            .collect::<Vec<RepoBranchFile>>();
+            .await?;
+            Some(last_commits)
+        } else {
+            None
+        };
 
         Ok(Repo {
-            objects: objects_in_repo,
+            objects: Self::parse_objects(entries, last_commits.as_deref()),
             read_me: None,
         })
     }
@@ -78,178 +133,220 @@ impl Repo {
     /// Get a repository at a specified state using a given repo name and branch.
     ///
     /// Called when a user clicks a repo from the list of repos on /git/ which default
-    /// to the master branch for now, but looking to add UI for branch selection soon.
-    pub async fn by_branch(repo: &str, branch: &str) -> Result<Repo, GitApiError> {
-        // Validate and construct safe repository path
-        let repo_path = validate_repo_path(REPOS_PATH, repo)?;
-        change_directory(repo_path.to_str().ok_or(GitApiError::InvalidInput(
-            "Invalid path encoding".to_string(),
-        ))?)?;
-
+    /// to the repo's default branch for now, but looking to add UI for branch selection soon.
+    /// The last commits are left out when they're fetched separately.
+    pub async fn by_branch(
+        backend: &dyn GitBackend,
+        last_commit_cache: &ByteCache<LastCommits>,
+        repo_path: &Path,
+        branch: &str,
+        with_last_commits: bool,
+    ) -> Result<Repo, GitApiError> {
         // Initiate a mutable variable to store README.md content
         // as a string if the repo has one else default to None.
         let mut read_me: Option<String> = None;
 
-        // Get all the objects in the repository by running
-        // the "git ls-tree {BRANCH}" command and parsing
-        // through the commands output
-        let objects = run_git_command(&["ls-tree", branch], false)?
-            .lines()
-            .filter_map(|object| {
-                if let Ok(object) = Self::parse_object(object, None) {
-                    // Check the object name to find a read me
-                    if object.name == "README.md" {
-                        // Try to read the "README.md" file and mutate
-                        // the `read_me` variable to it's content
-                        let branch_filename = format!("{}:README.md", &branch);
-                        match run_git_command(&["show", &branch_filename], false) {
-                            Ok(content) => {
-                                read_me = Some(content);
-                            }
-                            Err(e) => {
-                                eprintln!("{e:?}");
-                            }
-                        }
-                    };
-
-                    Some(object)
-                } else {
-                    None
+        // Pin the branch to a commit so every read sees the same state,
+        // there being none to pin it to in a repository without refs
+        let commit = match backend.resolve_ref(repo_path, branch).await {
+            Ok(commit) => commit,
+            Err(e) => {
+                if backend.refs(repo_path).await?.is_empty() {
+                    return Err(GitApiError::RepoWithNoCommits(Self::name_of(repo_path)));
                 }
-            })
-            .collect::<Vec<RepoBranchFile>>();
+                return Err(e);
+            }
+        };
 
-        Ok(Repo { objects, read_me })
+        // Get all the objects in the tree of the branch
+        let entries = backend.read_tree(repo_path, &commit).await?;
+
+        // Try to read the "README.md" file and mutate
+        // the `read_me` variable to it's content
+        if let Some(readme) = entries.iter().find(|entry| entry.name == "README.md") {
+            match backend.read_blob(repo_path, &readme.oid).await {
+                Ok(content) => {
+                    read_me = Some(String::from_utf8_lossy(&content).into_owned());
+                }
+                Err(e) => {
+                    log::warn!("{e}");
+                }
+            }
+        }
+
+        let last_commits = if with_last_commits {
+            let tree = backend
+                .resolve_ref(repo_path, &format!("{commit}^{{tree}}"))
+                .await?;
+            let last_commits =
+                LastCommits::of_tree(backend, last_commit_cache, repo_path, &commit, &tree, "")
+                    .await?;
+            Some(last_commits)
+        } else {
+            None
+        };
+
+        Ok(Repo {
+            objects: Self::parse_objects(entries, last_commits.as_deref()),
+            read_me,
+        })
     }
 
     /// Try to get the commit log of a specified repo at specified branch
     ///
     /// Called when a user clicks the `[ updates ]` button in a repo tree
-    /// the branch is hard coded to master for now, but looking to build
+    /// the branch is the repo's default branch for now, but looking to build
     /// UI for the user to select different branches soon.
-    pub async fn get_commit_log(repo: &str, branch: &str) -> Result<Vec<Commit>, GitApiError> {
-        // Validate and construct safe repository path
-        let repo_path = validate_repo_path(REPOS_PATH, repo)?;
-        let path = repo_path.to_str().ok_or(GitApiError::InvalidInput(
-            "Invalid path encoding".to_string(),
-        ))?;
-
-        // Get all the commit history using the "git log --no-merges {BRANCH}" command
-        // and parsing out commits from the output of the command
-        let log_output = run_git_command(
-            &[
-                "-C",
-                path,
-                "log",
-                "--no-merges",
-                branch,
-                "--date=iso-strict",
-                "--pretty=format:%x1e%H%x1f%an%x1f%ae%x1f%ad%x1f%P%x1f%B",
-            ],
-            false,
-        )?;
-
-        let commits: Vec<Commit> = log_output
-            .split('\x1e')
-            .filter(|part| !part.trim().is_empty())
-            .map(Commit::from)
-            .collect();
-
-        Ok(commits)
+    pub async fn get_commit_log(
+        backend: &dyn GitBackend,
+        repo_path: &Path,
+        branch: &str,
+    ) -> Result<Vec<Commit>, GitApiError> {
+        backend.log(repo_path, branch, None, None).await
     }
 
-    /// Converts files in a directory [`ReadDir`] into a result of repositories [`RepoData`].
-    fn into_repos_in_dir(files_in_dir: ReadDir) -> Result<Vec<RepoData>, GitApiError> {
-        // filter out any files that got an error trying to read and map the ok ones
-        // into repository data `RepoData`
-        files_in_dir
-            .filter_map(Result::ok)
-            .filter(|f| f.file_type().map(|ft| ft.is_dir()).unwrap_or(false))
-            .map(|entry| {
+    /// Resolve an omitted branch or `HEAD` into the branch `HEAD` points at.
+    ///
+    /// Other branches are returned as is, as is `HEAD` when it's detached.
+    pub async fn resolve_branch(
+        backend: &dyn GitBackend,
+        repo_path: &Path,
+        branch: Option<&str>,
+    ) -> Result<String, GitApiError> {
+        match branch {
+            Some(branch) if branch != "HEAD" => Ok(branch.to_string()),
+            _ => Ok(backend
+                .default_branch(repo_path)
+                .await?
+                .unwrap_or_else(|| "HEAD".to_string())),
+        }
+    }
+
+    /// Find the paths of every repository in the roots.
+    ///
+    /// Directories that aren't git repositories are skipped, as are
+    /// roots that can't be read.
+    fn find_all(roots: &[PathBuf]) -> Vec<PathBuf> {
+        let mut names = HashSet::new();
+        let mut repo_paths = Vec::new();
+
+        for root in roots {
+            let files_in_root = match fs::read_dir(root) {
+                Ok(files_in_root) => files_in_root,
+                Err(e) => {
+                    log::warn!("Can't read repository root {}: {e}", root.display());
+                    continue;
+                }
+            };
+
+            // filter out any files that got an error trying to read
+            // and anything that isn't a git repository
+            for entry in files_in_root
+                .filter_map(Result::ok)
+                .filter(|f| f.file_type().map(|ft| ft.is_dir()).unwrap_or(false))
+                .filter(|f| Self::is_git_repository(&f.path()))
+            {
                 let repo_path = entry.path();
-                let name = repo_path
-                    .strip_prefix(REPOS_PATH)
-                    .unwrap_or(repo_path.as_path())
-                    .file_stem()
-                    .and_then(|s| s.to_str())
-                    .unwrap_or_default()
-                    .to_owned();
-
-                let description =
-                    fs::read_to_string(repo_path.join("description")).unwrap_or_default();
-                let repo_path_str = repo_path.to_string_lossy();
-
-                let log_output = run_git_command(
-                    &[
-                        "-C",
-                        &repo_path_str,
-                        "log",
-                        "--no-merges",
-                        "master",
-                        "--date=iso-strict",
-                        "--pretty=format:%x1e%H%x1f%an%x1f%ae%x1f%ad%x1f%P%x1f%B",
-                    ],
-                    false,
-                )?;
-                let last_commit = log_output
-                    .split('\x1e')
-                    .find(|rec| !rec.trim().is_empty())
-                    .map(Commit::from)
-                    .ok_or(GitApiError::RepoWithNoCommits(
-                        repo_path.to_str().unwrap_or("<repo>").to_owned(),
-                    ))?;
-
-                Ok(RepoData {
-                    name,
-                    description,
-                    last_commit,
-                })
-            })
-            .collect::<Result<Vec<_>, GitApiError>>()
+                if names.insert(Self::name_of(&repo_path)) {
+                    repo_paths.push(repo_path);
+                }
+            }
+        }
+
+        repo_paths
     }
 
-    /// Try to parse a unparsed object string into a `RepoBranchFile`
-    fn parse_object(
-        unparsed_object: &str,
-        parent_path: Option<&str>,
-    ) -> Result<RepoBranchFile, GitApiError> {
-        // Split up the object line by spaces
-        let object_data: Vec<&str> = unparsed_object.split(' ').collect();
-
-        // Parse out a object name, hash, and file type
-        let hash_and_name: Vec<&str> = object_data[2].split('\t').collect();
-        let object_hash = hash_and_name[0].to_string();
-        let name = if let Some(parent_path) = parent_path {
-            format!("{}/{}", parent_path, hash_and_name[1])
-        } else {
-            hash_and_name[1].to_string()
+    /// Check if a directory looks like a (bare or not) git repository.
+    fn is_git_repository(path: &Path) -> bool {
+        let git_dir = path.join(".git");
+        let git_dir = if git_dir.is_dir() { &git_dir } else { path };
+
+        git_dir.join("HEAD").is_file() && git_dir.join("objects").is_dir()
+    }
+
+    /// Read the repository data (`RepoData`) of a repository, recording
+    /// why it couldn't be read instead of failing.
+    async fn load_data(backend: &dyn GitBackend, repo_path: &Path) -> RepoData {
+        let mut repo = RepoData {
+            name: Self::name_of(repo_path),
+            description: fs::read_to_string(repo_path.join("description")).unwrap_or_default(),
+            status: RepoStatus::Ok,
+            default_branch: None,
+            first_commit: None,
+            last_commit: None,
+            error: None,
         };
-        let file_type = object_data[1].to_string();
-
-        // Parse out the last commit from the commit log of the object
-        let log_output = run_git_command(
-            &[
-                "log",
-                "--no-merges",
-                "--date=iso-strict",
-                "--pretty=format:%x1e%H%x1f%an%x1f%ae%x1f%ad%x1f%P%x1f%B",
-                "--",
-                &name,
-            ],
-            false,
-        )?;
-        let last_commit = log_output
-            .split('\x1e')
-            .find(|rec| !rec.trim().is_empty())
-            .map(Commit::from)
-            .ok_or(GitApiError::RepoWithNoCommits(name.to_owned()))?;
-
-        Ok(RepoBranchFile {
-            name: hash_and_name[1].to_string(),
-            object_hash,
-            file_type,
-            last_commit,
-        })
+
+        match Self::load_commits(backend, repo_path, &mut repo).await {
+            Ok(Some(last_commit)) => repo.last_commit = Some(last_commit),
+            Ok(None) => repo.status = RepoStatus::Empty,
+            Err(e) => {
+                log::warn!("Can't read repository {}: {e}", repo_path.display());
+                repo.status = RepoStatus::Unreadable;
+                repo.error = Some(RepoError {
+                    code: e.code().to_string(),
+                    message: e.public_message(),
+                });
+            }
+        }
+
+        repo
+    }
+
+    /// Try to read the default branch, first and last commit of a
+    /// repository, returning the last one (`None` when the default
+    /// branch has no commits yet).
+    async fn load_commits(
+        backend: &dyn GitBackend,
+        repo_path: &Path,
+        repo: &mut RepoData,
+    ) -> Result<Option<Commit>, GitApiError> {
+        repo.default_branch = backend.default_branch(repo_path).await?;
+
+        // A default branch without a ref is unborn (no commits)
+        if let Some(default_branch) = &repo.default_branch {
+            let default_ref = format!("refs/heads/{default_branch}");
+            let refs = backend.refs(repo_path).await?;
+            if !refs.iter().any(|r| r.name == default_ref) {
+                return Ok(None);
+            }
+        }
+
+        let last_commit = backend.log(repo_path, "HEAD", None, Some(1)).await?;
+        if !last_commit.is_empty() {
+            repo.first_commit = backend.root_commit(repo_path, "HEAD").await?;
+        }
+
+        Ok(last_commit.into_iter().next())
+    }
+
+    /// The name of a repository is its directory name without `.git`
+    fn name_of(repo_path: &Path) -> String {
+        repo_path
+            .file_stem()
+            .and_then(|s| s.to_str())
+            .unwrap_or_default()
+            .to_owned()
+    }
+
+    /// Turn the entries of a tree into `RepoBranchFile`s, along with
+    /// their last commits when they were found
+    fn parse_objects(
+        entries: Vec<TreeEntry>,
+        last_commits: Option<&LastCommits>,
+    ) -> Vec<RepoBranchFile> {
+        entries
+            .into_iter()
+            .map(|entry| RepoBranchFile {
+                last_commit: last_commits
+                    .and_then(|last_commits| last_commits.commits.get(&entry.name))
+                    .cloned(),
+                name: entry.name,
+                object_hash: entry.oid,
+                file_type: entry.kind,
+                mode: entry.mode,
+            })
+            .collect()
     }
 }
 
@@ -259,7 +356,34 @@ impl Repo {
 pub struct RepoData {
     pub name: String,
     pub description: String,
-    pub last_commit: Commit,
+    pub status: RepoStatus,
+    /// The branch `HEAD` points at, `None` when it's detached
+    pub default_branch: Option<String>,
+    /// The first commit on the default branch, when the repository was created
+    pub first_commit: Option<Commit>,
+    /// The last commit on the default branch, `None` unless the status is ok
+    pub last_commit: Option<Commit>,
+    /// Why the repository couldn't be read when it's unreadable
+    pub error: Option<RepoError>,
+}
+
+#[derive(Serialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "camelCase")]
+/// Whether a repository could be read
+pub enum RepoStatus {
+    Ok,
+    /// The default branch has no commits yet
+    Empty,
+    /// Reading the repository failed
+    Unreadable,
+}
+
+#[derive(Serialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+/// A model representing why a repository couldn't be read
+pub struct RepoError {
+    pub code: String,
+    pub message: String,
 }
 
 #[derive(Serialize, Debug)]
@@ -269,6 +393,121 @@ pub struct RepoData {
 pub struct RepoBranchFile {
     pub name: String,
     pub file_type: String,
+    /// The file mode, e.g. `100644` or `040000`
+    pub mode: String,
     pub object_hash: String,
-    pub last_commit: Commit,
+    /// `None` when the last commits are fetched separately
+    pub last_commit: Option<Commit>,
+}
+impl Weigh for RepoBranchFile {
+    fn weigh(&self) -> usize {
+        self.name.weigh()
+            + self.file_type.weigh()
+            + self.mode.weigh()
+            + self.object_hash.weigh()
+            + self.last_commit.weigh()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        repository::backend::{fake::FakeBackend, GitRef},
+        utils::cache::revision_key,
+    };
+
+    fn backend() -> FakeBackend {
+        FakeBackend {
+            refs: vec![GitRef {
+                name: "refs/heads/master".to_string(),
+                target: "c2".to_string(),
+            }],
+            ..FakeBackend::default()
+        }
+        .tree_of("c2", "t2")
+        .tree("t2", &[("blob", "b1", "README.md"), ("tree", "t1", "src")])
+        .tree("t1", &[("blob", "b2", "main.rs")])
+        .blob("b1", "README.md", b"# Hello")
+        .blob("b2", "src/main.rs", b"fn main() {}")
+        .commit("c2aaaaaa", "Add main", &["src/main.rs"])
+        .commit("c1aaaaaa", "Add readme", &["README.md"])
+    }
+
+    #[tokio::test]
+    async fn test_by_branch_reads_objects_and_readme() {
+        let cache = ByteCache::new(1024 * 1024);
+        let repo = Repo::by_branch(&backend(), &cache, Path::new("repo"), "master", true)
+            .await
+            .unwrap();
+
+        let last_commit = |i: usize| repo.objects[i].last_commit.as_ref().unwrap();
+        assert_eq!(repo.read_me.as_deref(), Some("# Hello"));
+        assert_eq!(repo.objects.len(), 2);
+        assert_eq!(repo.objects[0].name, "README.md");
+        assert_eq!(last_commit(0).msg, "Add readme");
+        assert_eq!(repo.objects[1].file_type, "tree");
+        assert_eq!(repo.objects[1].mode, "040000");
+        assert_eq!(last_commit(1).msg, "Add main");
+        assert!(cache
+            .get(&revision_key(Path::new("repo"), "t2", "c2", ""))
+            .is_some());
+    }
+
+    #[tokio::test]
+    async fn test_last_commits_can_be_left_out() {
+        let cache = ByteCache::new(1024 * 1024);
+        let repo = Repo::by_branch(&backend(), &cache, Path::new("repo"), "master", false)
+            .await
+            .unwrap();
+
+        assert_eq!(repo.objects.len(), 2);
+        assert!(repo.objects.iter().all(|o| o.last_commit.is_none()));
+        assert_eq!(cache.stats().misses, 0);
+    }
+
+    #[tokio::test]
+    async fn test_by_hash_lists_a_sub_directory() {
+        let backend = backend().path("t1", "src");
+
+        let repo = Repo::by_hash(
+            &backend,
+            &PathIndex::default(),
+            &ByteCache::new(1024 * 1024),
+            Path::new("repo"),
+            "t1",
+            "c2",
+            true,
+        )
+        .await
+        .unwrap();
+
+        assert!(repo.read_me.is_none());
+        assert_eq!(repo.objects.len(), 1);
+        assert_eq!(repo.objects[0].name, "main.rs");
+        assert_eq!(repo.objects[0].last_commit.as_ref().unwrap().hash, "c2aaaa");
+    }
+
+    #[tokio::test]
+    async fn test_unknown_branch_is_not_found() {
+        let cache = ByteCache::new(1024 * 1024);
+        let result = Repo::by_branch(&backend(), &cache, Path::new("repo"), "nope", true).await;
+
+        assert!(matches!(result, Err(GitApiError::ObjectNotFound(_))));
+    }
+
+    #[tokio::test]
+    async fn test_repo_without_commits_is_a_conflict() {
+        let cache = ByteCache::new(1024 * 1024);
+        let result = Repo::by_branch(
+            &FakeBackend::default(),
+            &cache,
+            Path::new("empty.git"),
+            "master",
+            true,
+        )
+        .await;
+
+        assert!(matches!(result, Err(GitApiError::RepoWithNoCommits(name)) if name == "empty"));
+    }
 }
@@ -0,0 +1,286 @@
+use crate::repository::repo::RepoData;
+use notify::{Event, RecommendedWatcher, RecursiveMode, Watcher};
+use std::{
+    collections::{HashMap, HashSet},
+    fs,
+    future::Future,
+    path::{Component, Path, PathBuf},
+    sync::{Arc, Mutex},
+    time::{Duration, Instant, SystemTime},
+};
+
+/// What the refs of a repository looked like: the modification time of
+/// every file that changes when something is pushed.
+#[derive(Debug, Clone, PartialEq)]
+struct Fingerprint(Vec<(PathBuf, Option<SystemTime>)>);
+impl Fingerprint {
+    fn of(repo_path: &Path) -> Fingerprint {
+        let mut files = Vec::new();
+        for name in ["HEAD", "packed-refs", "description"] {
+            let path = repo_path.join(name);
+            let modified = fs::metadata(&path).and_then(|m| m.modified()).ok();
+            files.push((path, modified));
+        }
+
+        let mut dirs = vec![repo_path.join("refs")];
+        while let Some(dir) = dirs.pop() {
+            for entry in fs::read_dir(dir).into_iter().flatten().flatten() {
+                match entry.metadata() {
+                    Ok(metadata) if metadata.is_dir() => dirs.push(entry.path()),
+                    Ok(metadata) => files.push((entry.path(), metadata.modified().ok())),
+                    Err(_) => {}
+                }
+            }
+        }
+
+        files.sort();
+        Fingerprint(files)
+    }
+}
+
+/// The cached data of a single repository
+struct Entry {
+    fingerprint: Fingerprint,
+    data: RepoData,
+    checked_at: Instant,
+}
+
+/// A cache of the `RepoData` (name, description, and last commit) of
+/// every repository, each kept as long as its refs don't change.
+///
+/// When the repository roots are watched, a repository's refs are only
+/// checked again once the watcher saw it change or its entry is older
+/// than the TTL. Without a watcher they're checked on every lookup.
+pub struct RepoListCache {
+    entries: Mutex<HashMap<PathBuf, Entry>>,
+    /// Repositories the watcher saw change since they were last checked
+    changed: Arc<Mutex<HashSet<PathBuf>>>,
+    ttl: Duration,
+    watcher: Option<RecommendedWatcher>,
+}
+impl RepoListCache {
+    /// Create an empty cache, watching the roots for changes if asked to.
+    pub fn new(roots: &[PathBuf], ttl: Duration, watch: bool) -> RepoListCache {
+        let changed = Arc::new(Mutex::new(HashSet::new()));
+        let watcher = if watch {
+            Self::watch(roots, changed.clone())
+                .map_err(|e| log::warn!("Not watching the repository roots: {e}"))
+                .ok()
+        } else {
+            None
+        };
+
+        RepoListCache {
+            entries: Mutex::new(HashMap::new()),
+            changed,
+            ttl,
+            watcher,
+        }
+    }
+
+    /// Get the data of a repository from cache, or from `load` (which
+    /// is only polled if needed) when it's missing or stale.
+    pub async fn get_or_load(
+        &self,
+        repo_path: &Path,
+        load: impl Future<Output = RepoData>,
+    ) -> RepoData {
+        let changed = lock(&self.changed).remove(repo_path);
+
+        {
+            let mut entries = lock(&self.entries);
+            if let Some(entry) = entries.get_mut(repo_path) {
+                let trusted =
+                    self.watcher.is_some() && !changed && entry.checked_at.elapsed() < self.ttl;
+
+                if trusted {
+                    return entry.data.clone();
+                }
+                if entry.fingerprint == Fingerprint::of(repo_path) {
+                    entry.checked_at = Instant::now();
+                    return entry.data.clone();
+                }
+            }
+        }
+
+        // Fingerprint before loading so a push racing the load is
+        // picked up by the next lookup
+        let fingerprint = Fingerprint::of(repo_path);
+        let data = load.await;
+
+        lock(&self.entries).insert(
+            repo_path.to_path_buf(),
+            Entry {
+                fingerprint,
+                data: data.clone(),
+                checked_at: Instant::now(),
+            },
+        );
+
+        data
+    }
+
+    /// Forget every repository that isn't among the ones last found in
+    /// the roots, so removed repositories don't pile up.
+    pub fn retain(&self, repo_paths: &[PathBuf]) {
+        let found: HashSet<&Path> = repo_paths.iter().map(PathBuf::as_path).collect();
+
+        lock(&self.entries).retain(|repo_path, _| found.contains(repo_path.as_path()));
+        lock(&self.changed).retain(|repo_path| found.contains(repo_path.as_path()));
+    }
+
+    /// Watch the roots, recording which repositories changed.
+    fn watch(
+        roots: &[PathBuf],
+        changed: Arc<Mutex<HashSet<PathBuf>>>,
+    ) -> notify::Result<RecommendedWatcher> {
+        let watched_roots = roots.to_vec();
+        let mut watcher = notify::recommended_watcher(move |event: notify::Result<Event>| {
+            let Ok(event) = event else { return };
+
+            for path in &event.paths {
+                if let Some(repo_path) = repo_of(&watched_roots, path) {
+                    lock(&changed).insert(repo_path);
+                }
+            }
+        })?;
+
+        for root in roots {
+            watcher.watch(root, RecursiveMode::Recursive)?;
+        }
+
+        Ok(watcher)
+    }
+}
+
+/// Find the repository a changed path belongs to, ignoring changes
+/// that can't affect its `RepoData` (like new objects).
+fn repo_of(roots: &[PathBuf], path: &Path) -> Option<PathBuf> {
+    roots.iter().find_map(|root| {
+        let mut components = path.strip_prefix(root).ok()?.components();
+        let Some(Component::Normal(repo)) = components.next() else {
+            return None;
+        };
+
+        match components.next() {
+            Some(Component::Normal(dir)) if dir == "objects" || dir == "logs" => None,
+            _ => Some(root.join(repo)),
+        }
+    })
+}
+
+/// Lock a mutex, even if a thread panicked while holding it.
+fn lock<T>(mutex: &Mutex<T>) -> std::sync::MutexGuard<'_, T> {
+    mutex.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{repository::repo::RepoStatus, test_utils::Fixture, utils::commits::Commit};
+
+    fn repo_data(msg: &str) -> RepoData {
+        RepoData {
+            name: "alpha".to_string(),
+            description: String::new(),
+            status: RepoStatus::Ok,
+            default_branch: Some("master".to_string()),
+            first_commit: None,
+            last_commit: Some(Commit::new(
+                "abcdef",
+                "A",
+                "a@b.c",
+                "2024-01-02T03:04:05+00:00",
+                msg,
+            )),
+            error: None,
+        }
+    }
+
+    #[test]
+    fn test_repo_of_changed_path() {
+        let roots = [PathBuf::from("/repos")];
+
+        assert_eq!(
+            repo_of(&roots, Path::new("/repos/alpha.git/refs/heads/master")),
+            Some(PathBuf::from("/repos/alpha.git"))
+        );
+        assert_eq!(
+            repo_of(&roots, Path::new("/repos/alpha.git")),
+            Some(PathBuf::from("/repos/alpha.git"))
+        );
+        assert_eq!(
+            repo_of(&roots, Path::new("/repos/alpha.git/objects/ab")),
+            None
+        );
+        assert_eq!(
+            repo_of(&roots, Path::new("/elsewhere/alpha.git/HEAD")),
+            None
+        );
+    }
+
+    #[tokio::test]
+    async fn test_entries_are_reloaded_when_refs_change() {
+        let fixture = Fixture::new();
+        let repo_path = fixture.repos_root().join("alpha.git");
+        fs::create_dir_all(repo_path.join("refs/heads")).unwrap();
+        fs::write(repo_path.join("refs/heads/master"), "1").unwrap();
+
+        let cache = RepoListCache::new(&[fixture.repos_root()], Duration::from_secs(300), false);
+        let load = |msg| async move { repo_data(msg) };
+
+        let data = cache.get_or_load(&repo_path, load("first")).await;
+        assert_eq!(data.last_commit.unwrap().msg, "first");
+        let data = cache.get_or_load(&repo_path, load("second")).await;
+        assert_eq!(data.last_commit.unwrap().msg, "first");
+
+        // A new branch changes the fingerprint
+        fs::write(repo_path.join("refs/heads/feature"), "2").unwrap();
+        let data = cache.get_or_load(&repo_path, load("third")).await;
+        assert_eq!(data.last_commit.unwrap().msg, "third");
+    }
+
+    #[tokio::test]
+    async fn test_removed_repositories_are_forgotten() {
+        let fixture = Fixture::new();
+        let alpha = fixture.repos_root().join("alpha.git");
+        let beta = fixture.repos_root().join("beta.git");
+
+        let cache = RepoListCache::new(&[fixture.repos_root()], Duration::from_secs(300), false);
+        for repo_path in [&alpha, &beta] {
+            cache
+                .get_or_load(repo_path, async { repo_data("first") })
+                .await;
+        }
+        lock(&cache.changed).insert(beta.clone());
+
+        cache.retain(std::slice::from_ref(&alpha));
+        assert!(lock(&cache.entries).contains_key(&alpha));
+        assert!(!lock(&cache.entries).contains_key(&beta));
+        assert!(lock(&cache.changed).is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_watcher_picks_up_pushes() {
+        let fixture = Fixture::new();
+        let repo = fixture.repo("alpha");
+        repo.commit("first");
+
+        let cache = RepoListCache::new(&[fixture.repos_root()], Duration::from_secs(300), true);
+        assert!(cache.watcher.is_some());
+        let load = |msg| async move { repo_data(msg) };
+
+        cache.get_or_load(&repo.bare, load("first")).await;
+        repo.commit("second");
+
+        // Events are delivered asynchronously
+        for _ in 0..100 {
+            if lock(&cache.changed).contains(&repo.bare) {
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(20)).await;
+        }
+        let data = cache.get_or_load(&repo.bare, load("second")).await;
+        assert_eq!(data.last_commit.unwrap().msg, "second");
+    }
+}
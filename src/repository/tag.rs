@@ -0,0 +1,198 @@
+use crate::{
+    application::GitApiError,
+    repository::commit::{commits_in_range, count_commits},
+    utils::{
+        commands::run_git_command,
+        commits::Commit,
+        dates::{parse_date_to_string, parse_date_to_timestamp},
+        pagination::{paginate, PageInfo, PageQuery},
+    },
+};
+use semver::Version;
+use serde::Serialize;
+use std::{cmp::Ordering, path::Path};
+
+/// The `git for-each-ref` format every [`Tag`] is parsed from: the tagged
+/// object, the commit it peels to (annotated tags only) and the tag itself
+const TAG_FORMAT: &str = concat!(
+    "--format=%(refname:short)%1f%(objecttype)",
+    "%1f%(objectname)%1f%(authorname)%1f%(authoremail:trim)%1f%(authordate:iso-strict)%1f%(contents:subject)",
+    "%1f%(*objecttype)%1f%(*objectname)%1f%(*authorname)%1f%(*authoremail:trim)%1f%(*authordate:iso-strict)%1f%(*contents:subject)",
+    "%1f%(taggername)%1f%(taggeremail:trim)%1f%(creatordate:iso-strict)%1f%(contents:body)%1e",
+);
+
+/// The most commits listed for a single release
+const RELEASE_COMMIT_LIMIT: usize = 100;
+
+#[derive(Serialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+/// A model representing a (lightweight or annotated) tag in a repository
+pub struct Tag {
+    pub name: String,
+    pub annotated: bool,
+    /// The commit the tag points at
+    pub commit: Commit,
+    pub tagger: Option<String>,
+    pub tagger_email: Option<String>,
+    /// When the tag was made, the commit date for lightweight tags
+    pub date: String,
+    /// The annotation message of annotated tags
+    pub message: Option<String>,
+    #[serde(skip)]
+    pub timestamp: i64,
+}
+impl Tag {
+    /// Try to get every tag pointing at a commit in a repository, the
+    /// newest first.
+    ///
+    /// Tags named after semantic versions (optionally prefixed with `v`)
+    /// are sorted by version and come before the rest, sorted by date.
+    pub async fn get_all(repo_path: &Path) -> Result<Vec<Tag>, GitApiError> {
+        let output =
+            run_git_command(repo_path, &["for-each-ref", TAG_FORMAT, "refs/tags"], false).await?;
+
+        let mut tags: Vec<Tag> = output
+            .split('\x1e')
+            .filter_map(|record| Self::parse(record.trim_start_matches('\n')))
+            .collect();
+        tags.sort_by(|a, b| Self::compare(b, a));
+
+        Ok(tags)
+    }
+
+    /// Try to get a page of the tags of a repository.
+    pub async fn get_page(repo_path: &Path, query: &PageQuery) -> Result<TagPage, GitApiError> {
+        let (tags, page) = paginate(Self::get_all(repo_path).await?, query.page, query.per_page)?;
+
+        Ok(TagPage { tags, page })
+    }
+
+    /// Parse a record of `git for-each-ref` output in [`TAG_FORMAT`],
+    /// skipping tags of anything but commits.
+    fn parse(record: &str) -> Option<Tag> {
+        let fields: Vec<&str> = record.split('\x1f').collect();
+        let [name, kind, hash, author, email, date, subject, peeled_kind, peeled_hash, peeled_author, peeled_email, peeled_date, peeled_subject, tagger, tagger_email, created, body] =
+            fields.as_slice()
+        else {
+            return None;
+        };
+
+        let annotated = *kind == "tag";
+        let commit = match (annotated, *kind, *peeled_kind) {
+            (false, "commit", _) => Commit::new(hash, author, email, date, subject),
+            (true, _, "commit") => Commit::new(
+                peeled_hash,
+                peeled_author,
+                peeled_email,
+                peeled_date,
+                peeled_subject,
+            ),
+            _ => return None,
+        };
+
+        let message = annotated.then(|| {
+            if body.trim().is_empty() {
+                subject.to_string()
+            } else {
+                format!("{subject}\n\n{}", body.trim_end())
+            }
+        });
+
+        Some(Tag {
+            name: name.to_string(),
+            annotated,
+            commit,
+            tagger: annotated.then(|| tagger.to_string()),
+            tagger_email: annotated.then(|| tagger_email.to_string()),
+            date: parse_date_to_string(created.to_string()),
+            message,
+            timestamp: parse_date_to_timestamp(created),
+        })
+    }
+
+    /// Order tags by version when both are named after one, else by date.
+    fn compare(a: &Tag, b: &Tag) -> Ordering {
+        match (a.version(), b.version()) {
+            (Some(a_version), Some(b_version)) => a_version.cmp(&b_version),
+            (Some(_), None) => Ordering::Greater,
+            (None, Some(_)) => Ordering::Less,
+            (None, None) => a.timestamp.cmp(&b.timestamp),
+        }
+        .then_with(|| a.name.cmp(&b.name))
+    }
+
+    /// The semantic version the tag is named after, if any
+    fn version(&self) -> Option<Version> {
+        Version::parse(self.name.strip_prefix('v').unwrap_or(&self.name)).ok()
+    }
+}
+
+#[derive(Serialize, Debug)]
+#[serde(rename_all = "camelCase")]
+/// A model representing a page of the tags of a repository
+pub struct TagPage {
+    pub tags: Vec<Tag>,
+    #[serde(flatten)]
+    pub page: PageInfo,
+}
+
+#[derive(Serialize, Debug)]
+#[serde(rename_all = "camelCase")]
+/// A model representing a release: a tag and the commits since the previous one
+pub struct Release {
+    pub tag: Tag,
+    /// The name of the tag before this one, `None` for the first release
+    pub previous_tag: Option<String>,
+    /// How many (non merge) commits were made since the previous tag
+    pub commit_count: usize,
+    /// The newest of those commits, at most [`RELEASE_COMMIT_LIMIT`]
+    pub commits: Vec<Commit>,
+}
+impl Release {
+    /// Try to get a page of the releases of a repository, the newest first.
+    ///
+    /// When some tags are named after versions only those are releases,
+    /// so tags like `nightly` don't split the ranges between versions.
+    pub async fn get_page(repo_path: &Path, query: &PageQuery) -> Result<ReleasePage, GitApiError> {
+        let mut tags = Tag::get_all(repo_path).await?;
+        if tags.iter().any(|tag| tag.version().is_some()) {
+            tags.retain(|tag| tag.version().is_some());
+        }
+
+        // Pair every tag with the one before it before cutting the page
+        let previous_tags = tags.iter().skip(1).map(|tag| Some(tag.name.clone()));
+        let pairs: Vec<(Tag, Option<String>)> = tags
+            .iter()
+            .cloned()
+            .zip(previous_tags.chain([None]))
+            .collect();
+
+        let (pairs, page) = paginate(pairs, query.page, query.per_page)?;
+
+        let mut releases = Vec::new();
+        for (tag, previous_tag) in pairs {
+            let range = match &previous_tag {
+                Some(previous_tag) => format!("refs/tags/{previous_tag}..refs/tags/{}", tag.name),
+                None => format!("refs/tags/{}", tag.name),
+            };
+
+            releases.push(Release {
+                commit_count: count_commits(repo_path, &range).await?,
+                commits: commits_in_range(repo_path, &range, RELEASE_COMMIT_LIMIT).await?,
+                previous_tag,
+                tag,
+            });
+        }
+
+        Ok(ReleasePage { releases, page })
+    }
+}
+
+#[derive(Serialize, Debug)]
+#[serde(rename_all = "camelCase")]
+/// A model representing a page of the releases of a repository
+pub struct ReleasePage {
+    pub releases: Vec<Release>,
+    #[serde(flatten)]
+    pub page: PageInfo,
+}
@@ -0,0 +1,352 @@
+use crate::{
+    application::GitApiError,
+    repository::{backend::GitBackend, object::Object},
+    utils::commands::run_git_command,
+};
+use serde::{Deserialize, Serialize};
+use std::{collections::HashMap, path::Path};
+
+/// How many entries are listed when it's not asked for
+pub const DEFAULT_TREE_ENTRIES: usize = 1000;
+/// The most entries a single listing may hold
+pub const MAX_TREE_ENTRIES: usize = 10_000;
+
+#[derive(Serialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+/// A directory on the way from the root of a repository to a path
+pub struct Breadcrumb {
+    pub name: String,
+    /// The path of the directory from the root, empty for the root
+    pub path: String,
+}
+
+/// Split a path into the directories leading to it and itself, starting
+/// with the root.
+pub fn breadcrumbs(path: &str) -> Vec<Breadcrumb> {
+    let mut crumbs = vec![Breadcrumb {
+        name: String::new(),
+        path: String::new(),
+    }];
+    let mut end = 0;
+    for name in path.split('/').filter(|name| !name.is_empty()) {
+        end += name.len() + usize::from(end > 0);
+        crumbs.push(Breadcrumb {
+            name: name.to_string(),
+            path: path[..end].to_string(),
+        });
+    }
+
+    crumbs
+}
+
+#[derive(Deserialize, Debug, Default)]
+#[serde(default, rename_all = "camelCase", deny_unknown_fields)]
+/// The query string of directory listings, every field is optional
+pub struct TreeQuery {
+    /// Whether to list everything below the directory instead of only
+    /// its own entries
+    pub recursive: bool,
+    /// The most entries to list, at most [`MAX_TREE_ENTRIES`]
+    pub limit: Option<usize>,
+}
+impl TreeQuery {
+    /// Try to get how many entries to list.
+    fn limit(&self) -> Result<usize, GitApiError> {
+        match self.limit.unwrap_or(DEFAULT_TREE_ENTRIES) {
+            limit @ 1..=MAX_TREE_ENTRIES => Ok(limit),
+            _ => Err(GitApiError::InvalidInput(format!(
+                "The limit must be between 1 and {MAX_TREE_ENTRIES}"
+            ))),
+        }
+    }
+}
+
+#[derive(Serialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "camelCase")]
+/// What an entry of a tree is, told apart by its mode
+pub enum EntryKind {
+    Blob,
+    Executable,
+    Symlink,
+    Tree,
+    /// A submodule
+    Commit,
+}
+impl EntryKind {
+    fn from_mode(mode: &str) -> EntryKind {
+        match mode {
+            "040000" => EntryKind::Tree,
+            "160000" => EntryKind::Commit,
+            "120000" => EntryKind::Symlink,
+            "100755" => EntryKind::Executable,
+            _ => EntryKind::Blob,
+        }
+    }
+}
+
+#[derive(Serialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+/// An entry of a tree along with its path from the root
+pub struct TreeItem {
+    pub name: String,
+    pub path: String,
+    /// The file mode, e.g. `100644` or `040000`
+    pub mode: String,
+    pub kind: EntryKind,
+    pub oid: String,
+    /// The size in bytes, summed up for directories in recursive listings,
+    /// `None` for submodules and for directories otherwise
+    pub size: Option<u64>,
+}
+
+#[derive(Serialize, Debug)]
+#[serde(rename_all = "camelCase")]
+/// A model representing a directory of a repository at a revision
+pub struct PathTree {
+    /// The commit the path was read at
+    pub revision: String,
+    /// The path of the directory, empty for the root
+    pub path: String,
+    pub oid: String,
+    pub breadcrumbs: Vec<Breadcrumb>,
+    /// The size in bytes of everything below the directory, only for
+    /// recursive listings
+    pub size: Option<u64>,
+    pub entries: Vec<TreeItem>,
+    /// How many entries there are, listed or not
+    pub total: usize,
+    /// Whether entries were left out to stay within the limit
+    pub truncated: bool,
+}
+impl PathTree {
+    /// Try to list the directory at a path in a revision, optionally
+    /// along with everything below it.
+    pub async fn at_path(
+        backend: &dyn GitBackend,
+        repo_path: &Path,
+        rev: &str,
+        path: &str,
+        query: &TreeQuery,
+    ) -> Result<PathTree, GitApiError> {
+        let limit = query.limit()?;
+        let revision = backend
+            .resolve_ref(repo_path, &format!("{rev}^{{commit}}"))
+            .await?;
+
+        let oid = tree_at(backend, repo_path, &revision, path).await?;
+
+        // Directories are listed too, right before what's in them
+        let mut args = vec!["ls-tree", "-z", "--long"];
+        if query.recursive {
+            args.extend(["-r", "-t"]);
+        }
+        args.push(&oid);
+        let output = run_git_command(repo_path, &args, false).await?;
+
+        let mut entries = parse_ls_tree(&output, path)?;
+        let size = query.recursive.then(|| directory_sizes(&mut entries, path));
+
+        let total = entries.len();
+        entries.truncate(limit);
+
+        Ok(PathTree {
+            revision,
+            path: path.to_string(),
+            oid,
+            breadcrumbs: breadcrumbs(path),
+            size,
+            entries,
+            total,
+            truncated: total > limit,
+        })
+    }
+}
+
+/// Parse `git ls-tree -z --long` output, the paths in it being relative
+/// to `directory`.
+fn parse_ls_tree(output: &str, directory: &str) -> Result<Vec<TreeItem>, GitApiError> {
+    // Every entry looks like "<mode> <kind> <oid> <size>\t<path>\0", the
+    // size being padded and "-" for anything but blobs
+    output
+        .split('\0')
+        .filter(|entry| !entry.is_empty())
+        .map(|entry| {
+            let bad_entry = || GitApiError::Backend(format!("Bad tree entry {entry}"));
+            let (info, relative_path) = entry.split_once('\t').ok_or_else(bad_entry)?;
+            let mut info = info.split_whitespace();
+
+            match (info.next(), info.next(), info.next(), info.next()) {
+                (Some(mode), Some(_), Some(oid), Some(size)) => Ok(TreeItem {
+                    name: relative_path
+                        .rsplit('/')
+                        .next()
+                        .unwrap_or(relative_path)
+                        .to_string(),
+                    path: join_path(directory, relative_path),
+                    mode: mode.to_string(),
+                    kind: EntryKind::from_mode(mode),
+                    oid: oid.to_string(),
+                    size: size.parse().ok(),
+                }),
+                _ => Err(bad_entry()),
+            }
+        })
+        .collect()
+}
+
+/// Sum up the sizes of the files below every directory of a recursive
+/// listing, returning the size of the listed directory itself.
+fn directory_sizes(entries: &mut [TreeItem], directory: &str) -> u64 {
+    let mut sizes: HashMap<&str, u64> = HashMap::new();
+    let mut total = 0;
+    for entry in entries.iter() {
+        let Some(size) = entry.size else {
+            continue;
+        };
+        total += size;
+
+        // Every directory between the listed one and the file
+        let mut parent = entry.path.as_str();
+        while let Some((ancestor, _)) = parent.rsplit_once('/') {
+            if ancestor.len() <= directory.len() {
+                break;
+            }
+            *sizes.entry(ancestor).or_default() += size;
+            parent = ancestor;
+        }
+    }
+
+    let sizes: HashMap<String, u64> = sizes
+        .into_iter()
+        .map(|(path, size)| (path.to_string(), size))
+        .collect();
+    for entry in entries.iter_mut().filter(|e| e.kind == EntryKind::Tree) {
+        entry.size = Some(sizes.get(&entry.path).copied().unwrap_or(0));
+    }
+
+    total
+}
+
+#[derive(Serialize, Debug)]
+#[serde(rename_all = "camelCase")]
+/// A model representing a file of a repository at a revision
+pub struct PathBlob {
+    /// The commit the path was read at
+    pub revision: String,
+    pub path: String,
+    pub oid: String,
+    pub breadcrumbs: Vec<Breadcrumb>,
+    #[serde(flatten)]
+    pub object: Object,
+}
+impl PathBlob {
+    /// Try to read the file at a path in a revision.
+    pub async fn at_path(
+        backend: &dyn GitBackend,
+        repo_path: &Path,
+        rev: &str,
+        path: &str,
+    ) -> Result<PathBlob, GitApiError> {
+        let revision = backend
+            .resolve_ref(repo_path, &format!("{rev}^{{commit}}"))
+            .await?;
+
+        let oid = match backend.entry_at(repo_path, &revision, path).await? {
+            Some(entry) if entry.kind == "blob" => entry.oid,
+            Some(_) => return Err(GitApiError::InvalidInput(format!("{path} isn't a file"))),
+            None => return Err(GitApiError::ObjectNotFound(path.to_string())),
+        };
+        let object = Object::from_blob(backend, repo_path, &oid, path).await?;
+
+        Ok(PathBlob {
+            revision,
+            path: path.to_string(),
+            oid,
+            breadcrumbs: breadcrumbs(path),
+            object,
+        })
+    }
+}
+
+/// Try to get the id of the directory at a path in a revision.
+pub async fn tree_at(
+    backend: &dyn GitBackend,
+    repo_path: &Path,
+    revision: &str,
+    path: &str,
+) -> Result<String, GitApiError> {
+    match backend.entry_at(repo_path, revision, path).await? {
+        Some(entry) if entry.kind == "tree" => Ok(entry.oid),
+        Some(_) => Err(GitApiError::InvalidInput(format!(
+            "{path} isn't a directory"
+        ))),
+        None => Err(GitApiError::ObjectNotFound(path.to_string())),
+    }
+}
+
+/// Join a name onto a directory path, which is empty for the root.
+fn join_path(directory: &str, name: &str) -> String {
+    if directory.is_empty() {
+        name.to_string()
+    } else {
+        format!("{directory}/{name}")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_breadcrumbs() {
+        let crumb = |name: &str, path: &str| Breadcrumb {
+            name: name.to_string(),
+            path: path.to_string(),
+        };
+
+        assert_eq!(breadcrumbs(""), [crumb("", "")]);
+        assert_eq!(
+            breadcrumbs("src/utils/mod.rs"),
+            [
+                crumb("", ""),
+                crumb("src", "src"),
+                crumb("utils", "src/utils"),
+                crumb("mod.rs", "src/utils/mod.rs"),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_recursive_listing_sizes() {
+        let output = [
+            "040000 tree 1111111111111111111111111111111111111111       -\tdocs",
+            "100644 blob 2222222222222222222222222222222222222222      10\tdocs/a.md",
+            "040000 tree 3333333333333333333333333333333333333333       -\tdocs/deep",
+            "100755 blob 4444444444444444444444444444444444444444       5\tdocs/deep/run.sh",
+            "120000 blob 5555555555555555555555555555555555555555       7\tlink",
+            "160000 commit 6666666666666666666666666666666666666666       -\tvendor",
+            "",
+        ]
+        .join("\0");
+        let mut entries = parse_ls_tree(&output, "src").unwrap();
+        let total = directory_sizes(&mut entries, "src");
+
+        let listed: Vec<_> = entries
+            .iter()
+            .map(|e| (e.path.as_str(), e.kind, e.size))
+            .collect();
+        assert_eq!(
+            listed,
+            [
+                ("src/docs", EntryKind::Tree, Some(15)),
+                ("src/docs/a.md", EntryKind::Blob, Some(10)),
+                ("src/docs/deep", EntryKind::Tree, Some(5)),
+                ("src/docs/deep/run.sh", EntryKind::Executable, Some(5)),
+                ("src/link", EntryKind::Symlink, Some(7)),
+                ("src/vendor", EntryKind::Commit, None),
+            ]
+        );
+        assert_eq!(entries[3].name, "run.sh");
+        assert_eq!(total, 22);
+    }
+}
@@ -1,14 +1,33 @@
-use crate::routes;
+use crate::{application::GitApiError, routes};
 use actix_web::web;
 
 // Configures the server routes.
 pub fn configure_routes(cfg: &mut web::ServiceConfig) {
-    cfg.service(
+    // Report bad query strings like any other invalid input
+    cfg.app_data(
+        web::QueryConfig::default()
+            .error_handler(|err, _| GitApiError::InvalidInput(err.to_string()).into()),
+    )
+    .service(
         web::scope("/repo")
             .service(routes::repo::get_repositories)
             .service(routes::repo::get_repository_hash)
             .service(routes::repo::get_repository_branch)
-            .service(routes::repo::get_commit_log),
+            .service(routes::repo::get_tree)
+            .service(routes::repo::get_blob)
+            .service(routes::repo::get_last_commits)
+            .service(routes::repo::get_commit_log)
+            .service(routes::repo::get_commit)
+            .service(routes::repo::get_diff)
+            .service(routes::repo::get_comparison)
+            .service(routes::repo::get_merge_preview)
+            .service(routes::repo::get_blame)
+            .service(routes::repo::get_file_history)
+            .service(routes::repo::get_line_history)
+            .service(routes::repo::get_branches)
+            .service(routes::repo::get_tags)
+            .service(routes::repo::get_releases),
     )
-    .service(web::scope("/object").service(routes::object::get_object_content));
+    .service(web::scope("/object").service(routes::object::get_object_content))
+    .service(web::scope("/stats").service(routes::stats::get_cache_stats));
 }
@@ -1,3 +1,4 @@
 pub mod config;
 pub mod object;
 pub mod repo;
+pub mod stats;
@@ -1,49 +1,41 @@
 use crate::{
-    application::AppState,
+    application::{AppState, GitApiError},
     repository::object::Object,
     utils::{
-        responses::{internal_server_error, successful_response},
+        cache::object_key,
+        responses::successful_response,
         validation::{validate_hash, validate_repo_name},
     },
 };
-use actix_web::{get, web, web::Data, Responder};
+use actix_web::{get, web, web::Data, HttpResponse};
 
 /// Endpoint to get a objects content
 #[get("/by-hash/{repo}/{hash}")]
 pub async fn get_object_content(
     state: Data<AppState>,
     path: web::Path<(String, String)>,
-) -> impl Responder {
+) -> Result<HttpResponse, GitApiError> {
     // Consume path into variables
     let (repo_name, hash) = path.into_inner();
 
     // Validate user inputs
-    let repo_name = match validate_repo_name(&repo_name) {
-        Ok(name) => name,
-        Err(e) => {
-            eprintln!("Validation error: {e:?}");
-            return internal_server_error();
-        }
-    };
-    let hash = match validate_hash(&hash) {
-        Ok(hash) => hash,
-        Err(e) => {
-            eprintln!("Validation error: {e:?}");
-            return internal_server_error();
-        }
-    };
+    let repo_name = validate_repo_name(&repo_name)?;
+    let hash = validate_hash(&hash)?;
 
-    // Derive a key for the hash cache and try to fetch content
-    // from cache before trying to process the request
-    let hash_cache_key = format!("{}{}", repo_name, hash);
-    if let Some(cached_content) = &state.object_hash_cache.get(&hash_cache_key) {
-        return successful_response(&cached_content);
+    // Objects never change, so once the hash is resolved to a full
+    // object id try to fetch content from cache before trying to
+    // process the request
+    let repo_path = state.config.repos.resolve(repo_name)?;
+    let oid = state.backend.resolve_ref(&repo_path, hash).await?;
+    let cache_key = object_key(&repo_path, &oid);
+    if let Some(cached_content) = state.object_cache.get(&cache_key) {
+        return Ok(successful_response(&*cached_content));
     }
 
     // Try to get an specific objects content in a repo by a given hash
-    // and matching a response based on the result
-    match Object::by_hash(repo_name, hash).await {
-        Ok(object_content) => successful_response(&object_content),
-        Err(_) => internal_server_error(),
-    }
+    let object_content =
+        Object::by_hash(state.backend.as_ref(), &state.path_index, &repo_path, &oid).await?;
+    let object_content = state.object_cache.insert(cache_key, object_content);
+
+    Ok(successful_response(&*object_content))
 }
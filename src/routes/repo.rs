@@ -1,77 +1,109 @@
 use crate::{
-    application::AppState,
-    repository::repo::Repo,
+    application::{AppState, GitApiError},
+    repository::{
+        blame::{Blame, BlameQuery},
+        branch::Branch,
+        commit::CommitDetail,
+        compare::Comparison,
+        diff::{Diff, DiffQuery},
+        history::{FileHistory, HistoryQuery},
+        last_commit::LastCommits,
+        line_history::{LineHistory, LineHistoryQuery},
+        listing::RepoListQuery,
+        merge::MergePreview,
+        repo::{BranchQuery, HashQuery, Repo},
+        tag::{Release, Tag},
+        tree::{PathBlob, PathTree, TreeQuery},
+    },
     utils::{
-        responses::{internal_server_error, successful_response},
-        validation::{validate_branch_name, validate_hash, validate_repo_name},
+        cache::{object_key, revision_key},
+        pagination::PageQuery,
+        responses::successful_response,
+        validation::{validate_branch_name, validate_file_path, validate_hash, validate_repo_name},
     },
 };
 use actix_web::{
-    get,
-    web::{Data, Path},
-    Responder,
+    get, routes,
+    web::{Data, Path, Query},
+    HttpResponse,
 };
+use serde::Deserialize;
 
-/// Endpoint to get all repositories on the server
-#[get("/all")]
-pub async fn get_repositories(state: Data<AppState>) -> impl Responder {
-    let mut repos_cache = state.repos_cache.lock().await;
+#[derive(Deserialize, Debug)]
+/// The path of endpoints taking a repository and an optional branch
+pub struct BranchPath {
+    repo: String,
+    branch: Option<String>,
+}
 
-    // Check if repos cache is not empty that way
-    // it can just respond with that instead of having
-    // to fetch all the repos for every request.
-    if !repos_cache.is_empty() {
-        return successful_response(&*repos_cache);
-    }
+#[derive(Deserialize, Debug)]
+/// The path of diff endpoints, comparing `from` to `to` or, when
+/// `to` is omitted, the commit `from` to its first parent
+pub struct DiffPath {
+    repo: String,
+    from: String,
+    to: Option<String>,
+}
 
-    // Try to get all the repositories on my git server
-    // and match a response based on the result
-    match Repo::get_all().await {
-        Ok(repos) => {
-            // Update cache of repo's
-            *repos_cache = repos.clone();
+#[derive(Deserialize, Debug)]
+/// The path of endpoints addressing a path in a revision, the root
+/// when the path is omitted
+pub struct TreePath {
+    repo: String,
+    rev: String,
+    path: Option<String>,
+}
 
-            successful_response(&repos)
-        }
-        Err(e) => {
-            eprintln!("{e:?}");
-            internal_server_error()
-        }
-    }
+/// Endpoint to get a page of the repositories on the server,
+/// optionally filtered and sorted
+#[get("/all")]
+pub async fn get_repositories(
+    state: Data<AppState>,
+    query: Query<RepoListQuery>,
+) -> Result<HttpResponse, GitApiError> {
+    // Try to get all the repositories on my git server, only
+    // reading the ones that changed since they were cached
+    let repos = Repo::get_all(
+        state.backend.as_ref(),
+        &state.repos_cache,
+        &state.config.repos.roots,
+    )
+    .await?;
+
+    Ok(successful_response(&query.apply(repos)?))
 }
 
-/// Endpoint to get a specific repository at a specific branch
+/// Endpoint to get a specific repository at a specific branch,
+/// the default branch when it's omitted or `HEAD`
+#[routes]
+#[get("/by-branch/{repo}")]
 #[get("/by-branch/{repo}/{branch}")]
-pub async fn get_repository_branch(path: Path<(String, String)>) -> impl Responder {
+pub async fn get_repository_branch(
+    state: Data<AppState>,
+    path: Path<BranchPath>,
+    query: Query<BranchQuery>,
+) -> Result<HttpResponse, GitApiError> {
     // Consume path into variables
-    let (repo_name, branch) = path.into_inner();
+    let BranchPath { repo, branch } = path.into_inner();
 
     // Validate user inputs
-    let repo_name = match validate_repo_name(&repo_name) {
-        Ok(name) => name,
-        Err(e) => {
-            eprintln!("Validation error: {e:?}");
-            return internal_server_error();
-        }
-    };
-    let branch = match validate_branch_name(&branch) {
-        Ok(branch) => branch,
-        Err(e) => {
-            eprintln!("Validation error: {e:?}");
-            return internal_server_error();
-        }
-    };
+    let repo_name = validate_repo_name(&repo)?;
+    let branch = branch.as_deref().map(validate_branch_name).transpose()?;
+    let repo_path = state.config.repos.resolve(repo_name)?;
+    let branch = Repo::resolve_branch(state.backend.as_ref(), &repo_path, branch).await?;
 
     // Try to get all objects in the repo as well as an optional
-    // readme content string if the project has one and match a
-    // response based on the result
-    match Repo::by_branch(repo_name, branch).await {
-        Ok(repo) => successful_response(&repo),
-        Err(e) => {
-            eprintln!("{e:?}");
-            internal_server_error()
-        }
-    }
+    // readme content string if the project has one
+    let repo = Repo::by_branch(
+        state.backend.as_ref(),
+        &state.last_commit_cache,
+        &repo_path,
+        &branch,
+        query.last_commits,
+    )
+    .await?;
+
+    Ok(successful_response(&repo))
 }
 
 /// Endpoint to get a repository by a specific hash
@@ -79,76 +111,1364 @@ pub async fn get_repository_branch(path: Path<(String, String)>) -> impl Respond
 pub async fn get_repository_hash(
     state: Data<AppState>,
     path: Path<(String, String)>,
-) -> impl Responder {
+    query: Query<HashQuery>,
+) -> Result<HttpResponse, GitApiError> {
     // Extract repo name and hash from url path
     let (repo_name, hash) = path.into_inner();
 
     // Validate inputs
-    let repo_name = match validate_repo_name(&repo_name) {
-        Ok(name) => name,
-        Err(e) => {
-            eprintln!("Validation error: {e:?}");
-            return internal_server_error();
-        }
+    let repo_name = validate_repo_name(&repo_name)?;
+    let hash = validate_hash(&hash)?;
+    let rev = query.rev.as_deref().map(validate_branch_name).transpose()?;
+
+    // Trees and commits never change, so once the hash and revision are
+    // resolved to full object ids try to fetch content from cache before
+    // trying to process the request
+    let repo_path = state.config.repos.resolve(repo_name)?;
+    let oid = state.backend.resolve_ref(&repo_path, hash).await?;
+    let revision = state
+        .backend
+        .resolve_ref(&repo_path, &format!("{}^{{commit}}", rev.unwrap_or("HEAD")))
+        .await?;
+    let cache_key = if query.last_commits {
+        // The last commits depend on where the tree lives
+        let path = state
+            .path_index
+            .filename(state.backend.as_ref(), &repo_path, &oid)
+            .await?;
+        revision_key(&repo_path, &oid, &revision, &path)
+    } else {
+        object_key(&repo_path, &oid)
     };
-    let hash = match validate_hash(&hash) {
-        Ok(hash) => hash,
-        Err(e) => {
-            eprintln!("Validation error: {e:?}");
-            return internal_server_error();
-        }
+    if let Some(cached_content) = state.tree_cache.get(&cache_key) {
+        return Ok(successful_response(&*cached_content));
+    }
+
+    // Try to get all the objects in the repository by the hash
+    let repo = Repo::by_hash(
+        state.backend.as_ref(),
+        &state.path_index,
+        &state.last_commit_cache,
+        &repo_path,
+        &oid,
+        &revision,
+        query.last_commits,
+    )
+    .await?;
+    let repo = state.tree_cache.insert(cache_key, repo);
+
+    Ok(successful_response(&*repo))
+}
+
+/// Endpoint to list the directory at a path in a revision, the root
+/// when the path is omitted, optionally along with everything below it
+#[routes]
+#[get("/tree/{repo}/{rev}")]
+#[get("/tree/{repo}/{rev}/{path:.*}")]
+pub async fn get_tree(
+    state: Data<AppState>,
+    path: Path<TreePath>,
+    query: Query<TreeQuery>,
+) -> Result<HttpResponse, GitApiError> {
+    // Consume path into variables
+    let TreePath { repo, rev, path } = path.into_inner();
+
+    // Validate user inputs, revisions are either branch names or hashes
+    let repo_name = validate_repo_name(&repo)?;
+    let rev = validate_branch_name(&rev)?;
+    let path = match path.as_deref() {
+        None | Some("") => "",
+        Some(path) => validate_file_path(path)?,
     };
+    let repo_path = state.config.repos.resolve(repo_name)?;
 
-    // Derive a key for the hash cache and try to fetch content
-    // from cache before trying to process the request
-    let hash_cache_key = format!("{}{}", repo_name, hash);
-    if let Some(cached_content) = state.repo_hash_cache.get(&hash_cache_key) {
-        return successful_response(&cached_content);
-    }
+    // Try to list the directory the path points at
+    let tree = PathTree::at_path(state.backend.as_ref(), &repo_path, rev, path, &query).await?;
 
-    // Try to get all the objects in the repository by
-    // the hash and match a response to the result
-    match Repo::by_hash(repo_name, hash).await {
-        Ok(repo) => successful_response(&repo),
-        Err(e) => {
-            eprintln!("{e:?}");
-            internal_server_error()
-        }
-    }
+    Ok(successful_response(&tree))
 }
 
-/// Endpoint to get a repository's commit log
+/// Endpoint to get the file at a path in a revision
+#[get("/blob/{repo}/{rev}/{path:.*}")]
+pub async fn get_blob(
+    state: Data<AppState>,
+    path: Path<(String, String, String)>,
+) -> Result<HttpResponse, GitApiError> {
+    // Extract repo name, revision and file path from url path
+    let (repo_name, rev, file_path) = path.into_inner();
+
+    // Validate user inputs, revisions are either branch names or hashes
+    let repo_name = validate_repo_name(&repo_name)?;
+    let rev = validate_branch_name(&rev)?;
+    let file_path = validate_file_path(&file_path)?;
+    let repo_path = state.config.repos.resolve(repo_name)?;
+
+    // Try to read the file the path points at
+    let blob = PathBlob::at_path(state.backend.as_ref(), &repo_path, rev, file_path).await?;
+
+    Ok(successful_response(&blob))
+}
+
+/// Endpoint to get the last commit that changed every entry of the
+/// directory at a path in a revision, the root when the path is omitted,
+/// for listings that left them out
+#[routes]
+#[get("/last-commits/{repo}/{rev}")]
+#[get("/last-commits/{repo}/{rev}/{path:.*}")]
+pub async fn get_last_commits(
+    state: Data<AppState>,
+    path: Path<TreePath>,
+) -> Result<HttpResponse, GitApiError> {
+    // Consume path into variables
+    let TreePath { repo, rev, path } = path.into_inner();
+
+    // Validate user inputs, revisions are either branch names or hashes
+    let repo_name = validate_repo_name(&repo)?;
+    let rev = validate_branch_name(&rev)?;
+    let path = match path.as_deref() {
+        None | Some("") => "",
+        Some(path) => validate_file_path(path)?,
+    };
+    let repo_path = state.config.repos.resolve(repo_name)?;
+
+    // Try to find the last commits, walking the history at most once
+    // for every directory and revision
+    let last_commits = LastCommits::at_path(
+        state.backend.as_ref(),
+        &state.last_commit_cache,
+        &repo_path,
+        rev,
+        path,
+    )
+    .await?;
+
+    Ok(successful_response(&*last_commits))
+}
+
+/// Endpoint to get a repository's commit log for a branch,
+/// the default branch when it's omitted or `HEAD`
+#[routes]
+#[get("/commit-log/{repo}")]
 #[get("/commit-log/{repo}/{branch}")]
 pub async fn get_commit_log(
-    _state: Data<AppState>,
+    state: Data<AppState>,
+    path: Path<BranchPath>,
+) -> Result<HttpResponse, GitApiError> {
+    // Consume path into variables
+    let BranchPath { repo, branch } = path.into_inner();
+
+    // Validate user inputs
+    let repo_name = validate_repo_name(&repo)?;
+    let branch = branch.as_deref().map(validate_branch_name).transpose()?;
+    let repo_path = state.config.repos.resolve(repo_name)?;
+    let branch = Repo::resolve_branch(state.backend.as_ref(), &repo_path, branch).await?;
+
+    // Try to get a repo's commit log for a branch
+    let commits = Repo::get_commit_log(state.backend.as_ref(), &repo_path, &branch).await?;
+
+    Ok(successful_response(&commits))
+}
+
+/// Endpoint to get everything about a single commit, including
+/// the files it changed
+#[get("/commit/{repo}/{hash}")]
+pub async fn get_commit(
+    state: Data<AppState>,
     path: Path<(String, String)>,
-) -> impl Responder {
+) -> Result<HttpResponse, GitApiError> {
+    // Extract repo name and hash from url path
+    let (repo_name, hash) = path.into_inner();
+
+    // Validate inputs
+    let repo_name = validate_repo_name(&repo_name)?;
+    let hash = validate_hash(&hash)?;
+    let repo_path = state.config.repos.resolve(repo_name)?;
+
+    // Try to get the commit along with its changed files
+    let commit = CommitDetail::by_hash(state.backend.as_ref(), &repo_path, hash).await?;
+
+    Ok(successful_response(&commit))
+}
+
+/// Endpoint to get the changes between two revisions, or made
+/// by a single commit, as hunks of numbered lines
+#[routes]
+#[get("/diff/{repo}/{from}")]
+#[get("/diff/{repo}/{from}/{to}")]
+pub async fn get_diff(
+    state: Data<AppState>,
+    path: Path<DiffPath>,
+    query: Query<DiffQuery>,
+) -> Result<HttpResponse, GitApiError> {
     // Consume path into variables
-    let (repo_name, branch) = path.into_inner();
+    let DiffPath { repo, from, to } = path.into_inner();
+
+    // Validate user inputs, revisions are either branch names or hashes
+    let repo_name = validate_repo_name(&repo)?;
+    let from = validate_branch_name(&from)?;
+    let to = to.as_deref().map(validate_branch_name).transpose()?;
+    let repo_path = state.config.repos.resolve(repo_name)?;
+
+    // Try to diff the revisions, or the commit against its parent
+    let backend = state.backend.as_ref();
+    let diff = match to {
+        Some(to) => Diff::between(backend, &repo_path, from, to, &query).await?,
+        None => Diff::of_commit(backend, &repo_path, from, &query).await?,
+    };
+
+    Ok(successful_response(&diff))
+}
+
+/// Endpoint to compare two revisions given as `{base}...{head}`, with
+/// the commits and changes head would bring into base
+#[get("/compare/{repo}/{range:.*}")]
+pub async fn get_comparison(
+    state: Data<AppState>,
+    path: Path<(String, String)>,
+) -> Result<HttpResponse, GitApiError> {
+    // Extract repo name and range from url path
+    let (repo_name, range) = path.into_inner();
+
+    // Validate user inputs, revisions are either branch names or hashes
+    let repo_name = validate_repo_name(&repo_name)?;
+    let (base, head) = split_range(&range)?;
+    let repo_path = state.config.repos.resolve(repo_name)?;
+
+    // Try to get the commits and changed files since the merge base
+    let comparison = Comparison::between(state.backend.as_ref(), &repo_path, base, head).await?;
+
+    Ok(successful_response(&comparison))
+}
+
+/// Endpoint to preview merging the branches `{base}...{head}` (head
+/// into base) without touching any refs
+#[get("/merge-preview/{repo}/{range:.*}")]
+pub async fn get_merge_preview(
+    state: Data<AppState>,
+    path: Path<(String, String)>,
+) -> Result<HttpResponse, GitApiError> {
+    // Extract repo name and range from url path
+    let (repo_name, range) = path.into_inner();
 
     // Validate user inputs
-    let repo_name = match validate_repo_name(&repo_name) {
-        Ok(name) => name,
-        Err(e) => {
-            eprintln!("Validation error: {e:?}");
-            return internal_server_error();
-        }
+    let repo_name = validate_repo_name(&repo_name)?;
+    let (base, head) = split_range(&range)?;
+    let repo_path = state.config.repos.resolve(repo_name)?;
+
+    // Try to merge in memory and report any conflicts
+    let preview = MergePreview::between(state.backend.as_ref(), &repo_path, base, head).await?;
+
+    Ok(successful_response(&preview))
+}
+
+/// Endpoint to get who last changed every line of a file at a revision,
+/// optionally for a range of lines
+#[get("/blame/{repo}/{rev}/{path:.*}")]
+pub async fn get_blame(
+    state: Data<AppState>,
+    path: Path<(String, String, String)>,
+    query: Query<BlameQuery>,
+) -> Result<HttpResponse, GitApiError> {
+    // Extract repo name, revision and file path from url path
+    let (repo_name, rev, file_path) = path.into_inner();
+
+    // Validate user inputs, revisions are either branch names or hashes
+    let repo_name = validate_repo_name(&repo_name)?;
+    let rev = validate_branch_name(&rev)?;
+    let file_path = validate_file_path(&file_path)?;
+    let repo_path = state.config.repos.resolve(repo_name)?;
+
+    // Try to blame the file, skipping the revisions it says to ignore
+    let blame = Blame::of_file(state.backend.as_ref(), &repo_path, rev, file_path, &query).await?;
+
+    Ok(successful_response(&blame))
+}
+
+/// Endpoint to get a page of the commits that changed a file up to a
+/// revision, following it across renames
+#[get("/history/{repo}/{rev}/{path:.*}")]
+pub async fn get_file_history(
+    state: Data<AppState>,
+    path: Path<(String, String, String)>,
+    query: Query<HistoryQuery>,
+) -> Result<HttpResponse, GitApiError> {
+    // Extract repo name, revision and file path from url path
+    let (repo_name, rev, file_path) = path.into_inner();
+
+    // Validate user inputs, revisions are either branch names or hashes
+    let repo_name = validate_repo_name(&repo_name)?;
+    let rev = validate_branch_name(&rev)?;
+    let file_path = validate_file_path(&file_path)?;
+    let repo_path = state.config.repos.resolve(repo_name)?;
+
+    // Try to get the commits along with where the file was at each
+    let history =
+        FileHistory::of_file(state.backend.as_ref(), &repo_path, rev, file_path, &query).await?;
+
+    Ok(successful_response(&history))
+}
+
+/// Endpoint to get the commits that changed a range of lines or a
+/// function of a file up to a revision, with how they changed it
+#[get("/line-history/{repo}/{rev}/{path:.*}")]
+pub async fn get_line_history(
+    state: Data<AppState>,
+    path: Path<(String, String, String)>,
+    query: Query<LineHistoryQuery>,
+) -> Result<HttpResponse, GitApiError> {
+    // Extract repo name, revision and file path from url path
+    let (repo_name, rev, file_path) = path.into_inner();
+
+    // Validate user inputs, revisions are either branch names or hashes
+    let repo_name = validate_repo_name(&repo_name)?;
+    let rev = validate_branch_name(&rev)?;
+    let file_path = validate_file_path(&file_path)?;
+    let repo_path = state.config.repos.resolve(repo_name)?;
+
+    // Try to follow the lines back through the commits changing them
+    let history =
+        LineHistory::of_lines(state.backend.as_ref(), &repo_path, rev, file_path, &query).await?;
+
+    Ok(successful_response(&history))
+}
+
+/// Endpoint to get a page of a repository's branches
+#[get("/branches/{repo}")]
+pub async fn get_branches(
+    state: Data<AppState>,
+    path: Path<String>,
+    query: Query<PageQuery>,
+) -> Result<HttpResponse, GitApiError> {
+    // Validate user inputs
+    let repo_name = validate_repo_name(&path)?;
+    let repo_path = state.config.repos.resolve(repo_name)?;
+
+    // Try to get the branches along with how far they are from the default
+    let branches = Branch::get_page(state.backend.as_ref(), &repo_path, &query).await?;
+
+    Ok(successful_response(&branches))
+}
+
+/// Endpoint to get a page of a repository's tags, the newest first
+#[get("/tags/{repo}")]
+pub async fn get_tags(
+    state: Data<AppState>,
+    path: Path<String>,
+    query: Query<PageQuery>,
+) -> Result<HttpResponse, GitApiError> {
+    // Validate user inputs
+    let repo_name = validate_repo_name(&path)?;
+    let repo_path = state.config.repos.resolve(repo_name)?;
+
+    // Try to get the tags along with the commits they point at
+    let tags = Tag::get_page(&repo_path, &query).await?;
+
+    Ok(successful_response(&tags))
+}
+
+/// Endpoint to get a page of a repository's releases, every tag
+/// with the commits made since the tag before it
+#[get("/releases/{repo}")]
+pub async fn get_releases(
+    state: Data<AppState>,
+    path: Path<String>,
+    query: Query<PageQuery>,
+) -> Result<HttpResponse, GitApiError> {
+    // Validate user inputs
+    let repo_name = validate_repo_name(&path)?;
+    let repo_path = state.config.repos.resolve(repo_name)?;
+
+    // Try to get the releases along with their commit ranges
+    let releases = Release::get_page(&repo_path, &query).await?;
+
+    Ok(successful_response(&releases))
+}
+
+/// Split and validate a `{base}...{head}` range of revisions.
+fn split_range(range: &str) -> Result<(&str, &str), GitApiError> {
+    let (base, head) = range
+        .split_once("...")
+        .ok_or_else(|| GitApiError::InvalidInput("Range must look like base...head".to_string()))?;
+
+    Ok((validate_branch_name(base)?, validate_branch_name(head)?))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        application::AppState,
+        repository::diff::{MAX_FILES, MAX_PATCH_BYTES},
+        routes::config::configure_routes,
+        test_utils::{git, Fixture},
     };
-    let branch = match validate_branch_name(&branch) {
-        Ok(branch) => branch,
-        Err(e) => {
-            eprintln!("Validation error: {e:?}");
-            return internal_server_error();
-        }
+    use actix_web::{
+        http::StatusCode,
+        test::{call_and_read_body_json, call_service, init_service, TestRequest},
+        web::Data,
+        App,
+    };
+    use std::{
+        fs::{self, Permissions},
+        os::unix::fs::PermissionsExt,
     };
 
-    // Try to get a repo's commit log for a branch
-    // and matching a response based on the result
-    match Repo::get_commit_log(repo_name, branch).await {
-        Ok(commits) => successful_response(&commits),
-        Err(e) => {
-            eprintln!("{e:?}");
-            internal_server_error()
+    #[test]
+    fn test_concurrent_requests_stay_in_their_repository() {
+        let fixture = Fixture::new();
+        for name in ["alpha", "beta"] {
+            fixture
+                .repo(name)
+                .write(&format!("{name}.txt"), name)
+                .commit("initial commit");
+        }
+        let state = Data::new(AppState::new(fixture.config()));
+
+        // Hammer both repositories from several threads, each with its own
+        // runtime, so requests for different repositories truly interleave.
+        std::thread::scope(|scope| {
+            for i in 0..8 {
+                let state = state.clone();
+                scope.spawn(move || {
+                    actix_web::rt::System::new().block_on(async move {
+                        let app =
+                            init_service(App::new().app_data(state).configure(configure_routes))
+                                .await;
+                        let name = if i % 2 == 0 { "alpha" } else { "beta" };
+
+                        for _ in 0..10 {
+                            let req = TestRequest::get()
+                                .uri(&format!("/repo/by-branch/{name}/master"))
+                                .to_request();
+                            let body: serde_json::Value = call_and_read_body_json(&app, req).await;
+
+                            assert_eq!(body["objects"][0]["name"], format!("{name}.txt"));
+                        }
+                    });
+                });
+            }
+        });
+    }
+
+    #[actix_web::test]
+    async fn test_default_branch_is_read_from_head() {
+        let fixture = Fixture::new();
+        let repo = fixture.repo("gamma");
+        repo.git(&["checkout", "-q", "-b", "main"]);
+        repo.write("main.txt", "main").commit("on main");
+        git(&repo.bare, &["symbolic-ref", "HEAD", "refs/heads/main"]);
+
+        let state = Data::new(AppState::new(fixture.config()));
+        let app = init_service(App::new().app_data(state).configure(configure_routes)).await;
+
+        let req = TestRequest::get().uri("/repo/all").to_request();
+        let body: serde_json::Value = call_and_read_body_json(&app, req).await;
+        assert_eq!(body["repos"][0]["defaultBranch"], "main");
+        assert_eq!(body["repos"][0]["lastCommit"]["msg"], "on main");
+
+        for uri in ["/repo/by-branch/gamma", "/repo/by-branch/gamma/HEAD"] {
+            let req = TestRequest::get().uri(uri).to_request();
+            let body: serde_json::Value = call_and_read_body_json(&app, req).await;
+            assert_eq!(body["objects"][0]["name"], "main.txt", "{uri}");
+        }
+
+        let req = TestRequest::get()
+            .uri("/repo/commit-log/gamma")
+            .to_request();
+        let body: serde_json::Value = call_and_read_body_json(&app, req).await;
+        assert_eq!(body[0]["msg"], "on main");
+    }
+
+    #[actix_web::test]
+    async fn test_broken_repositories_dont_hide_the_rest() {
+        let fixture = Fixture::new();
+        fixture
+            .repo("ok")
+            .write("a.txt", "a")
+            .commit("initial commit");
+        fixture.repo("empty");
+        let corrupt = fixture.repo("corrupt");
+        std::fs::write(corrupt.bare.join("HEAD"), format!("{}\n", "1".repeat(40))).unwrap();
+        std::fs::create_dir(fixture.repos_root().join("not-a-repo")).unwrap();
+
+        let state = Data::new(AppState::new(fixture.config()));
+        let app = init_service(App::new().app_data(state).configure(configure_routes)).await;
+
+        let req = TestRequest::get().uri("/repo/all").to_request();
+        let body: serde_json::Value = call_and_read_body_json(&app, req).await;
+        let repos = body["repos"].as_array().unwrap();
+        assert_eq!(repos.len(), 3);
+
+        let repo = |name: &str| repos.iter().find(|r| r["name"] == name).unwrap();
+        assert_eq!(repo("ok")["status"], "ok");
+        assert_eq!(repo("ok")["lastCommit"]["msg"], "initial commit");
+        assert_eq!(repo("empty")["status"], "empty");
+        assert!(repo("empty")["lastCommit"].is_null());
+        assert_eq!(repo("corrupt")["status"], "unreadable");
+        assert!(repo("corrupt")["error"]["code"].is_string());
+
+        let req = TestRequest::get()
+            .uri("/repo/all?perPage=many")
+            .to_request();
+        let res = call_service(&app, req).await;
+        assert_eq!(res.status(), StatusCode::BAD_REQUEST);
+    }
+
+    #[actix_web::test]
+    async fn test_branches_are_compared_to_the_default_branch() {
+        let fixture = Fixture::new();
+        let repo = fixture.repo("alpha");
+        repo.write("a.txt", "a").commit("first");
+        repo.git(&["checkout", "-q", "-b", "feature"]);
+        repo.write("b.txt", "b").commit("on feature");
+        repo.git(&["checkout", "-q", "master"]);
+        repo.write("c.txt", "c").commit("on master 1");
+        repo.write("d.txt", "d").commit("on master 2");
+
+        let state = Data::new(AppState::new(fixture.config()));
+        let app = init_service(App::new().app_data(state).configure(configure_routes)).await;
+
+        let req = TestRequest::get().uri("/repo/branches/alpha").to_request();
+        let body: serde_json::Value = call_and_read_body_json(&app, req).await;
+        assert_eq!(body["total"], 2);
+
+        let master = &body["branches"][0];
+        assert_eq!(master["name"], "master");
+        assert_eq!(master["isDefault"], true);
+        assert_eq!(master["commit"]["msg"], "on master 2");
+
+        let feature = &body["branches"][1];
+        assert_eq!(feature["name"], "feature");
+        assert_eq!(feature["isDefault"], false);
+        assert_eq!(
+            (&feature["ahead"], &feature["behind"]),
+            (&1.into(), &2.into())
+        );
+
+        let req = TestRequest::get()
+            .uri("/repo/branches/alpha?perPage=1&page=2")
+            .to_request();
+        let body: serde_json::Value = call_and_read_body_json(&app, req).await;
+        assert_eq!(body["branches"][0]["name"], "feature");
+    }
+
+    #[actix_web::test]
+    async fn test_tags_and_releases() {
+        let fixture = Fixture::new();
+        let repo = fixture.repo("alpha");
+        let bare = repo.bare.to_str().unwrap().to_string();
+        repo.write("a.txt", "a").commit("first");
+        repo.git(&["tag", "v0.9.0"]);
+        repo.write("b.txt", "b").commit("second");
+        repo.write("c.txt", "c").commit("third");
+        repo.git(&["tag", "-a", "v0.10.0", "-m", "Release 0.10\n\nWith notes"]);
+        repo.git(&["tag", "nightly"]);
+        repo.git(&["push", "-q", &bare, "--tags"]);
+
+        let state = Data::new(AppState::new(fixture.config()));
+        let app = init_service(App::new().app_data(state).configure(configure_routes)).await;
+
+        let req = TestRequest::get().uri("/repo/tags/alpha").to_request();
+        let body: serde_json::Value = call_and_read_body_json(&app, req).await;
+        assert_eq!(body["total"], 3);
+
+        // Versions are compared as versions, not strings
+        let names: Vec<_> = body["tags"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|tag| tag["name"].as_str().unwrap())
+            .collect();
+        assert_eq!(names, ["v0.10.0", "v0.9.0", "nightly"]);
+
+        let annotated = &body["tags"][0];
+        assert_eq!(annotated["annotated"], true);
+        assert_eq!(annotated["tagger"], "Test Committer");
+        assert_eq!(annotated["message"], "Release 0.10\n\nWith notes");
+        assert_eq!(annotated["commit"]["msg"], "third");
+
+        let lightweight = &body["tags"][1];
+        assert_eq!(lightweight["annotated"], false);
+        assert!(lightweight["tagger"].is_null());
+        assert_eq!(lightweight["commit"]["msg"], "first");
+
+        // Only versions are releases when there are any
+        let req = TestRequest::get().uri("/repo/releases/alpha").to_request();
+        let body: serde_json::Value = call_and_read_body_json(&app, req).await;
+        assert_eq!(body["total"], 2);
+
+        let release = &body["releases"][0];
+        assert_eq!(release["tag"]["name"], "v0.10.0");
+        assert_eq!(release["previousTag"], "v0.9.0");
+        assert_eq!(release["commitCount"], 2);
+        assert_eq!(release["commits"][0]["msg"], "third");
+        assert_eq!(release["commits"][1]["msg"], "second");
+
+        let first_release = &body["releases"][1];
+        assert_eq!(first_release["tag"]["name"], "v0.9.0");
+        assert!(first_release["previousTag"].is_null());
+        assert_eq!(first_release["commitCount"], 1);
+    }
+
+    #[actix_web::test]
+    async fn test_commit_detail() {
+        let fixture = Fixture::new();
+        let repo = fixture.repo("alpha");
+        let first = repo.write("a.txt", "one\ntwo\n").commit("first");
+        repo.git(&["mv", "a.txt", "b.txt"]);
+        repo.write("c.txt", "three\n");
+        let second = repo.commit(
+            "second\n\nWith a body.\n\nSigned-off-by: A <a@example.com>\nCo-authored-by: B <b@example.com>",
+        );
+
+        let state = Data::new(AppState::new(fixture.config()));
+        let app = init_service(App::new().app_data(state).configure(configure_routes)).await;
+
+        let req = TestRequest::get()
+            .uri(&format!("/repo/commit/alpha/{}", &second[..8]))
+            .to_request();
+        let body: serde_json::Value = call_and_read_body_json(&app, req).await;
+        assert_eq!(body["hash"], second);
+        assert!(second.starts_with(body["shortHash"].as_str().unwrap()));
+        assert_eq!(body["parents"], serde_json::json!([first]));
+        assert_eq!(body["author"]["name"], "Test Author");
+        assert_eq!(body["committer"]["email"], "committer@example.com");
+        assert_eq!(body["subject"], "second");
+        assert!(body["message"].as_str().unwrap().contains("With a body."));
+        assert_eq!(body["trailers"][0]["key"], "Signed-off-by");
+        assert_eq!(body["trailers"][1]["value"], "B <b@example.com>");
+
+        let files = body["files"].as_array().unwrap();
+        assert_eq!(files.len(), 2);
+        assert_eq!(files[0]["path"], "b.txt");
+        assert_eq!(files[0]["oldPath"], "a.txt");
+        assert_eq!(files[0]["status"], "renamed");
+        assert_eq!(files[1]["path"], "c.txt");
+        assert_eq!(files[1]["status"], "added");
+        assert_eq!(files[1]["additions"], 1);
+
+        // The root commit is compared to nothing
+        let req = TestRequest::get()
+            .uri(&format!("/repo/commit/alpha/{first}"))
+            .to_request();
+        let body: serde_json::Value = call_and_read_body_json(&app, req).await;
+        assert_eq!(body["parents"], serde_json::json!([]));
+        assert_eq!(body["files"][0]["additions"], 2);
+
+        // Only commits have details
+        let tree = repo.git(&["rev-parse", "HEAD^{tree}"]);
+        let req = TestRequest::get()
+            .uri(&format!("/repo/commit/alpha/{tree}"))
+            .to_request();
+        let res = call_service(&app, req).await;
+        assert!(res.status().is_client_error());
+    }
+
+    #[actix_web::test]
+    async fn test_diffs() {
+        let fixture = Fixture::new();
+        let repo = fixture.repo("alpha");
+        let first = repo
+            .write("a.txt", "one\ntwo\nthree\n")
+            .write("old.txt", "a file that is long enough to be renamed\n")
+            .commit("first");
+        repo.git(&["mv", "old.txt", "new.txt"]);
+        let second = repo
+            .write("a.txt", "one\n  two!\nthree\n")
+            .write("logo.png", "\u{0}\u{1}")
+            .commit("second");
+
+        let state = Data::new(AppState::new(fixture.config()));
+        let app = init_service(App::new().app_data(state).configure(configure_routes)).await;
+
+        let req = TestRequest::get()
+            .uri(&format!("/repo/diff/alpha/{first}/{second}?context=0"))
+            .to_request();
+        let body: serde_json::Value = call_and_read_body_json(&app, req).await;
+        assert_eq!(body["from"], first);
+        let files = body["files"].as_array().unwrap();
+        let file = |path: &str| files.iter().find(|f| f["path"] == path).unwrap();
+
+        let text = file("a.txt");
+        assert_eq!(text["status"], "modified");
+        assert_eq!(
+            (&text["additions"], &text["deletions"]),
+            (&1.into(), &1.into())
+        );
+        let lines = &text["hunks"][0]["lines"];
+        assert_eq!(lines[0]["kind"], "deleted");
+        assert_eq!(lines[0]["oldLine"], 2);
+        assert_eq!(lines[1]["kind"], "added");
+        assert_eq!(lines[1]["content"], "  two!");
+        // Only the indentation and the punctuation are new
+        assert_eq!(lines[0]["changes"], serde_json::json!([]));
+        assert_eq!(
+            lines[1]["changes"],
+            serde_json::json!([{ "start": 0, "end": 2 }, { "start": 5, "end": 6 }])
+        );
+
+        assert_eq!(file("new.txt")["status"], "renamed");
+        assert_eq!(file("new.txt")["oldPath"], "old.txt");
+        assert_eq!(file("logo.png")["binary"], true);
+        assert_eq!(file("logo.png")["hunks"], serde_json::json!([]));
+
+        // The commit shortcut compares against the parent with context
+        let req = TestRequest::get()
+            .uri(&format!("/repo/diff/alpha/{second}"))
+            .to_request();
+        let body: serde_json::Value = call_and_read_body_json(&app, req).await;
+        assert_eq!(body["from"], first);
+        assert_eq!(body["files"].as_array().unwrap().len(), 3);
+
+        // Whitespace only changes leave files without hunks
+        let third = repo
+            .write("a.txt", "one\ntwo!\nthree\n")
+            .write("tab\tname.txt", "x\n")
+            .commit("third");
+        let req = TestRequest::get()
+            .uri(&format!(
+                "/repo/diff/alpha/{second}/{third}?whitespace=ignoreAll"
+            ))
+            .to_request();
+        let body: serde_json::Value = call_and_read_body_json(&app, req).await;
+        assert_eq!(body["files"][0]["path"], "a.txt");
+        assert_eq!(body["files"][0]["hunks"], serde_json::json!([]));
+        assert_eq!(body["files"][1]["path"], "tab\tname.txt");
+        assert_eq!(body["files"][1]["additions"], 1);
+
+        // Root commits are compared to nothing
+        let req = TestRequest::get()
+            .uri(&format!("/repo/diff/alpha/{first}"))
+            .to_request();
+        let body: serde_json::Value = call_and_read_body_json(&app, req).await;
+        assert!(body["from"].is_null());
+        assert_eq!(body["files"][0]["status"], "added");
+
+        for uri in [
+            "/repo/diff/alpha/master?context=5000",
+            "/repo/diff/alpha/master?whitespace=sometimes",
+            "/repo/diff/alpha/--output=x",
+        ] {
+            let req = TestRequest::get().uri(uri).to_request();
+            let res = call_service(&app, req).await;
+            assert_eq!(res.status(), StatusCode::BAD_REQUEST, "{uri}");
+        }
+    }
+
+    #[actix_web::test]
+    async fn test_large_diffs_are_truncated() {
+        let fixture = Fixture::new();
+        let repo = fixture.repo("alpha");
+        for i in 0..=MAX_FILES {
+            repo.write(&format!("file{i:04}.txt"), "x\n");
+        }
+        let first = repo.commit("first");
+        repo.write("a.txt", "a\n")
+            .write("big.txt", &"y\n".repeat(MAX_PATCH_BYTES / 2))
+            .write("c.txt", "c\n");
+        let second = repo.commit("second");
+
+        let state = Data::new(AppState::new(fixture.config()));
+        let app = init_service(App::new().app_data(state).configure(configure_routes)).await;
+
+        // Too many files
+        let req = TestRequest::get()
+            .uri(&format!("/repo/diff/alpha/{first}"))
+            .to_request();
+        let body: serde_json::Value = call_and_read_body_json(&app, req).await;
+        assert_eq!(body["files"].as_array().unwrap().len(), MAX_FILES);
+        assert_eq!(body["truncated"], true);
+
+        // Too large patches, the files after them being left out
+        let req = TestRequest::get()
+            .uri(&format!("/repo/diff/alpha/{second}"))
+            .to_request();
+        let body: serde_json::Value = call_and_read_body_json(&app, req).await;
+        let files = body["files"].as_array().unwrap();
+        assert_eq!(files.len(), 1);
+        assert_eq!(files[0]["path"], "a.txt");
+        assert_eq!(body["truncated"], true);
+
+        let req = TestRequest::get()
+            .uri(&format!("/repo/diff/alpha/{first}/{second}"))
+            .to_request();
+        let body: serde_json::Value = call_and_read_body_json(&app, req).await;
+        assert_eq!(body["truncated"], true);
+    }
+
+    #[actix_web::test]
+    async fn test_diffs_of_type_changes() {
+        let fixture = Fixture::new();
+        let repo = fixture.repo("alpha");
+        repo.write("target.txt", "target\n")
+            .write("link", "a file for now\n")
+            .commit("first");
+        fs::remove_file(repo.work.join("link")).unwrap();
+        std::os::unix::fs::symlink("target.txt", repo.work.join("link")).unwrap();
+        let second = repo.commit("second");
+
+        let state = Data::new(AppState::new(fixture.config()));
+        let app = init_service(App::new().app_data(state).configure(configure_routes)).await;
+
+        // The file is patched twice, once as a file and once as a symlink
+        let req = TestRequest::get()
+            .uri(&format!("/repo/diff/alpha/{second}"))
+            .to_request();
+        let body: serde_json::Value = call_and_read_body_json(&app, req).await;
+        let files = body["files"].as_array().unwrap();
+        assert_eq!(files.len(), 1);
+        assert_eq!(files[0]["status"], "typeChanged");
+        assert_eq!(files[0]["newMode"], "120000");
+        assert_eq!(
+            (&files[0]["additions"], &files[0]["deletions"]),
+            (&1.into(), &1.into())
+        );
+        assert_eq!(files[0]["hunks"].as_array().unwrap().len(), 2);
+
+        // And listed once in the commit's files
+        let req = TestRequest::get()
+            .uri(&format!("/repo/commit/alpha/{second}"))
+            .to_request();
+        let body: serde_json::Value = call_and_read_body_json(&app, req).await;
+        let files = body["files"].as_array().unwrap();
+        assert_eq!(files.len(), 1);
+        assert_eq!(files[0]["status"], "typeChanged");
+        assert_eq!(
+            (&files[0]["additions"], &files[0]["deletions"]),
+            (&1.into(), &1.into())
+        );
+    }
+
+    #[actix_web::test]
+    async fn test_compare() {
+        let fixture = Fixture::new();
+        let repo = fixture.repo("alpha");
+        let base = repo.write("a.txt", "a\n").commit("first");
+        repo.git(&["checkout", "-q", "-b", "feature"]);
+        repo.write("a.txt", "a\nb\n").commit("feature 1");
+        repo.write("c.txt", "c\n").commit("feature 2");
+        repo.git(&["checkout", "-q", "master"]);
+        repo.write("d.txt", "d\n").commit("on master");
+
+        let state = Data::new(AppState::new(fixture.config()));
+        let app = init_service(App::new().app_data(state).configure(configure_routes)).await;
+
+        let req = TestRequest::get()
+            .uri("/repo/compare/alpha/master...feature")
+            .to_request();
+        let body: serde_json::Value = call_and_read_body_json(&app, req).await;
+        assert_eq!(body["mergeBase"], base);
+        assert_eq!(
+            (&body["aheadBy"], &body["behindBy"]),
+            (&2.into(), &1.into())
+        );
+        assert_eq!(body["commits"][0]["msg"], "feature 2");
+        assert_eq!(body["commits"][1]["msg"], "feature 1");
+
+        // Changes on master since the merge base aren't included
+        let paths: Vec<_> = body["files"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|file| file["path"].as_str().unwrap())
+            .collect();
+        assert_eq!(paths, ["a.txt", "c.txt"]);
+        assert_eq!(
+            body["stats"],
+            serde_json::json!({ "filesChanged": 2, "additions": 2, "deletions": 0 })
+        );
+
+        // Branch names can have slashes on either side
+        git(&repo.bare, &["branch", "topic/login", "feature"]);
+        for (uri, ahead_by, behind_by) in [
+            ("/repo/compare/alpha/master...topic/login", 2, 1),
+            ("/repo/compare/alpha/topic/login...master", 1, 2),
+        ] {
+            let req = TestRequest::get().uri(uri).to_request();
+            let body: serde_json::Value = call_and_read_body_json(&app, req).await;
+            assert_eq!(
+                (&body["aheadBy"], &body["behindBy"]),
+                (&ahead_by.into(), &behind_by.into()),
+                "{uri}"
+            );
+        }
+
+        for uri in [
+            "/repo/compare/alpha/master..feature",
+            "/repo/compare/alpha/master...-x",
+        ] {
+            let req = TestRequest::get().uri(uri).to_request();
+            let res = call_service(&app, req).await;
+            assert_eq!(res.status(), StatusCode::BAD_REQUEST, "{uri}");
+        }
+    }
+
+    #[actix_web::test]
+    async fn test_merge_preview() {
+        let fixture = Fixture::new();
+        let repo = fixture.repo("alpha");
+        repo.write("a.txt", "a\n")
+            .write("b.txt", "b\n")
+            .commit("first");
+        repo.git(&["checkout", "-q", "-b", "clean"]);
+        repo.write("c.txt", "c\n").commit("add c");
+        repo.git(&["checkout", "-q", "-b", "conflict", "master"]);
+        repo.write("a.txt", "theirs\n").commit("change a");
+        repo.git(&["rm", "-q", "b.txt"]);
+        repo.commit("delete b");
+        repo.git(&["checkout", "-q", "master"]);
+        repo.write("a.txt", "ours\n")
+            .write("b.txt", "ours\n")
+            .commit("change a and b");
+        let master = git(&repo.bare, &["rev-parse", "master"]);
+
+        let state = Data::new(AppState::new(fixture.config()));
+        let app = init_service(App::new().app_data(state).configure(configure_routes)).await;
+
+        let req = TestRequest::get()
+            .uri("/repo/merge-preview/alpha/master...clean")
+            .to_request();
+        let body: serde_json::Value = call_and_read_body_json(&app, req).await;
+        assert_eq!(body["status"], "clean");
+        assert!(body["tree"].is_string());
+        assert_eq!(body["conflicts"], serde_json::json!([]));
+
+        let req = TestRequest::get()
+            .uri("/repo/merge-preview/alpha/master...conflict")
+            .to_request();
+        let body: serde_json::Value = call_and_read_body_json(&app, req).await;
+        assert_eq!(body["status"], "conflicted");
+        assert!(body["tree"].is_null());
+        assert_eq!(body["conflicts"][0]["path"], "a.txt");
+        assert_eq!(body["conflicts"][0]["kind"], "contents");
+        assert_eq!(body["conflicts"][1]["path"], "b.txt");
+        assert_eq!(body["conflicts"][1]["kind"], "modify/delete");
+
+        // Nothing was moved
+        assert_eq!(git(&repo.bare, &["rev-parse", "master"]), master);
+
+        // Branch names can have slashes on either side
+        git(&repo.bare, &["branch", "topic/clean", "clean"]);
+        git(&repo.bare, &["branch", "release/1.0", "master"]);
+        let req = TestRequest::get()
+            .uri("/repo/merge-preview/alpha/release/1.0...topic/clean")
+            .to_request();
+        let body: serde_json::Value = call_and_read_body_json(&app, req).await;
+        assert_eq!(body["status"], "clean");
+
+        let req = TestRequest::get()
+            .uri("/repo/merge-preview/alpha/master...-x")
+            .to_request();
+        let res = call_service(&app, req).await;
+        assert_eq!(res.status(), StatusCode::BAD_REQUEST);
+    }
+
+    #[actix_web::test]
+    async fn test_blame() {
+        let fixture = Fixture::new();
+        let repo = fixture.repo("alpha");
+        repo.write("src/lib.rs", "fn a() {}\nfn b() {}\n")
+            .commit("first");
+        repo.write("src/lib.rs", "fn a() {}\nfn b() {}\nfn c() {}\n")
+            .commit("add c");
+        let format = repo
+            .write("src/lib.rs", "fn a() { }\nfn b() {}\nfn c() {}\n")
+            .commit("format");
+        repo.write(
+            ".git-blame-ignore-revs",
+            &format!("# formatting\n{format}\n"),
+        )
+        .commit("ignore formatting");
+
+        let state = Data::new(AppState::new(fixture.config()));
+        let app = init_service(App::new().app_data(state).configure(configure_routes)).await;
+
+        let req = TestRequest::get()
+            .uri("/repo/blame/alpha/master/src/lib.rs")
+            .to_request();
+        let body: serde_json::Value = call_and_read_body_json(&app, req).await;
+        assert_eq!(body["ignoredRevs"], true);
+        let hunks = body["hunks"].as_array().unwrap();
+        assert_eq!(hunks.len(), 2);
+        assert_eq!(hunks[0]["commit"]["msg"], "first");
+        assert_eq!(
+            hunks[0]["lines"],
+            serde_json::json!(["fn a() { }", "fn b() {}"])
+        );
+        assert_eq!(hunks[1]["commit"]["msg"], "add c");
+        assert_eq!(hunks[1]["startLine"], 3);
+
+        let req = TestRequest::get()
+            .uri("/repo/blame/alpha/master/src/lib.rs?ignoreRevs=false&start=1&end=1")
+            .to_request();
+        let body: serde_json::Value = call_and_read_body_json(&app, req).await;
+        assert_eq!(body["hunks"][0]["commit"]["msg"], "format");
+        assert_eq!(body["hunks"].as_array().unwrap().len(), 1);
+
+        for (uri, status) in [
+            (
+                "/repo/blame/alpha/master/src/missing.rs",
+                StatusCode::NOT_FOUND,
+            ),
+            (
+                "/repo/blame/alpha/master/src/../../etc",
+                StatusCode::BAD_REQUEST,
+            ),
+            (
+                "/repo/blame/alpha/master/:(glob)src/*",
+                StatusCode::BAD_REQUEST,
+            ),
+            (
+                "/repo/blame/alpha/master/src/lib.rs?start=10",
+                StatusCode::BAD_REQUEST,
+            ),
+        ] {
+            let req = TestRequest::get().uri(uri).to_request();
+            let res = call_service(&app, req).await;
+            assert_eq!(res.status(), status, "{uri}");
+        }
+
+        // Ignore lists naming anything but commits are rejected by name
+        for rev in ["--all", "deadbeef"] {
+            repo.write(".git-blame-ignore-revs", &format!("{format}\n{rev}\n"))
+                .commit("ignore more");
+
+            let req = TestRequest::get()
+                .uri("/repo/blame/alpha/master/src/lib.rs")
+                .to_request();
+            let res = call_service(&app, req).await;
+            assert_eq!(res.status(), StatusCode::BAD_REQUEST, "{rev}");
+            let body: serde_json::Value = actix_web::test::read_body_json(res).await;
+            let message = body["error"]["message"].as_str().unwrap();
+            assert!(message.contains(rev), "{message}");
+        }
+    }
+
+    #[actix_web::test]
+    async fn test_file_history_follows_renames() {
+        let fixture = Fixture::new();
+        let repo = fixture.repo("alpha");
+        repo.write("old.txt", "1\n2\n3\n").commit("create");
+        repo.write("other.txt", "x\n").commit("unrelated");
+        repo.git(&["mv", "old.txt", "new.txt"]);
+        repo.commit("rename");
+        repo.write("new.txt", "1\n2\n3\n4\n").commit("append");
+
+        let state = Data::new(AppState::new(fixture.config()));
+        let app = init_service(App::new().app_data(state).configure(configure_routes)).await;
+
+        let req = TestRequest::get()
+            .uri("/repo/history/alpha/master/new.txt?stats=true")
+            .to_request();
+        let body: serde_json::Value = call_and_read_body_json(&app, req).await;
+        assert_eq!(body["hasMore"], false);
+
+        let commits = body["commits"].as_array().unwrap();
+        let messages: Vec<_> = commits.iter().map(|c| &c["commit"]["msg"]).collect();
+        assert_eq!(messages, ["append", "rename", "create"]);
+        assert_eq!(commits[0]["additions"], 1);
+        assert_eq!(commits[1]["status"], "renamed");
+        assert_eq!(commits[1]["oldPath"], "old.txt");
+        assert_eq!(commits[2]["path"], "old.txt");
+        assert_eq!(commits[2]["additions"], 3);
+
+        // Pages past the rename still follow it
+        let req = TestRequest::get()
+            .uri("/repo/history/alpha/master/new.txt?perPage=1&page=2")
+            .to_request();
+        let body: serde_json::Value = call_and_read_body_json(&app, req).await;
+        assert_eq!(body["commits"][0]["commit"]["msg"], "rename");
+        assert_eq!(body["hasMore"], true);
+
+        let req = TestRequest::get()
+            .uri("/repo/history/alpha/master/new.txt?perPage=1&page=3")
+            .to_request();
+        let body: serde_json::Value = call_and_read_body_json(&app, req).await;
+        assert_eq!(body["commits"][0]["commit"]["msg"], "create");
+        assert!(body["commits"][0]["additions"].is_null());
+        assert_eq!(body["hasMore"], false);
+
+        // Paths aren't patterns
+        let req = TestRequest::get()
+            .uri("/repo/history/alpha/master/*.txt")
+            .to_request();
+        let body: serde_json::Value = call_and_read_body_json(&app, req).await;
+        assert_eq!(body["commits"], serde_json::json!([]));
+    }
+
+    #[actix_web::test]
+    async fn test_line_history() {
+        let fixture = Fixture::new();
+        let repo = fixture.repo("alpha");
+        repo.write(
+            "lib.rs",
+            "fn main() {\n    a();\n}\n\nfn other() {\n    b();\n}\n",
+        )
+        .commit("add lib");
+        repo.write(
+            "lib.rs",
+            "fn main() {\n    a(1);\n}\n\nfn other() {\n    b();\n}\n",
+        )
+        .commit("tweak main");
+        repo.write(
+            "lib.rs",
+            "fn main() {\n    a(1);\n}\n\nfn other() {\n    b(2);\n}\n",
+        )
+        .commit("tweak other");
+
+        let state = Data::new(AppState::new(fixture.config()));
+        let app = init_service(App::new().app_data(state).configure(configure_routes)).await;
+
+        let req = TestRequest::get()
+            .uri("/repo/line-history/alpha/master/lib.rs?function=%5Efn%20main")
+            .to_request();
+        let body: serde_json::Value = call_and_read_body_json(&app, req).await;
+        let commits = body["commits"].as_array().unwrap();
+        let messages: Vec<_> = commits.iter().map(|c| &c["commit"]["msg"]).collect();
+        assert_eq!(messages, ["tweak main", "add lib"]);
+        assert_eq!(commits[0]["hunks"][0]["lines"][1]["content"], "    a();");
+
+        let req = TestRequest::get()
+            .uri("/repo/line-history/alpha/master/lib.rs?start=6&end=6")
+            .to_request();
+        let body: serde_json::Value = call_and_read_body_json(&app, req).await;
+        assert_eq!(body["commits"][0]["commit"]["msg"], "tweak other");
+        assert_eq!(body["commits"].as_array().unwrap().len(), 2);
+
+        for uri in [
+            "/repo/line-history/alpha/master/lib.rs",
+            "/repo/line-history/alpha/master/lib.rs?start=20&end=30",
+            "/repo/line-history/alpha/master/lib.rs?function=nothing",
+        ] {
+            let res = call_service(&app, TestRequest::get().uri(uri).to_request()).await;
+            assert_eq!(res.status(), StatusCode::BAD_REQUEST, "{uri}");
+        }
+
+        let req = TestRequest::get()
+            .uri("/repo/line-history/alpha/master/nope.rs?start=1&end=2")
+            .to_request();
+        let res = call_service(&app, req).await;
+        assert_eq!(res.status(), StatusCode::NOT_FOUND);
+    }
+
+    #[actix_web::test]
+    async fn test_path_addressed_trees_and_blobs() {
+        let fixture = Fixture::new();
+        let repo = fixture.repo("alpha");
+        repo.write("README.md", "# Alpha\n")
+            .write("src/utils/mod.rs", "pub mod x;\n")
+            .write("-dash.txt", "dash\n")
+            .commit("Initial commit");
+
+        let state = Data::new(AppState::new(fixture.config()));
+        let app = init_service(App::new().app_data(state).configure(configure_routes)).await;
+
+        let req = TestRequest::get()
+            .uri("/repo/tree/alpha/master")
+            .to_request();
+        let body: serde_json::Value = call_and_read_body_json(&app, req).await;
+        assert_eq!(body["path"], "");
+        let paths: Vec<_> = body["entries"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|e| &e["path"])
+            .collect();
+        assert_eq!(paths, ["-dash.txt", "README.md", "src"]);
+
+        let req = TestRequest::get()
+            .uri("/repo/tree/alpha/master/src/utils")
+            .to_request();
+        let body: serde_json::Value = call_and_read_body_json(&app, req).await;
+        assert_eq!(body["entries"][0]["path"], "src/utils/mod.rs");
+        assert_eq!(body["entries"][0]["kind"], "blob");
+        assert_eq!(body["breadcrumbs"][2]["path"], "src/utils");
+
+        let req = TestRequest::get()
+            .uri("/repo/blob/alpha/master/src/utils/mod.rs")
+            .to_request();
+        let body: serde_json::Value = call_and_read_body_json(&app, req).await;
+        assert_eq!(body["content"], "pub mod x;\n");
+        assert_eq!(body["ext"], "rs");
+        assert_eq!(body["breadcrumbs"].as_array().unwrap().len(), 4);
+
+        let req = TestRequest::get()
+            .uri("/repo/blob/alpha/master/-dash.txt")
+            .to_request();
+        let body: serde_json::Value = call_and_read_body_json(&app, req).await;
+        assert_eq!(body["content"], "dash\n");
+
+        for (uri, status) in [
+            ("/repo/blob/alpha/master/src", StatusCode::BAD_REQUEST),
+            ("/repo/tree/alpha/master/README.md", StatusCode::BAD_REQUEST),
+            ("/repo/tree/alpha/master/src/../..", StatusCode::BAD_REQUEST),
+            (
+                "/repo/blob/alpha/--output=x/README.md",
+                StatusCode::BAD_REQUEST,
+            ),
+            ("/repo/blob/alpha/master/missing.md", StatusCode::NOT_FOUND),
+            ("/repo/tree/alpha/nope/src", StatusCode::NOT_FOUND),
+        ] {
+            let res = call_service(&app, TestRequest::get().uri(uri).to_request()).await;
+            assert_eq!(res.status(), status, "{uri}");
+        }
+    }
+
+    #[actix_web::test]
+    async fn test_recursive_tree_listing() {
+        let fixture = Fixture::new();
+        let repo = fixture.repo("alpha");
+        repo.write("README.md", "# Alpha\n")
+            .write("src/main.rs", "fn main() {}\n")
+            .write("src/bin/run.sh", "#!/bin/sh\n");
+        let script = repo.work.join("src/bin/run.sh");
+        fs::set_permissions(&script, Permissions::from_mode(0o755)).unwrap();
+        repo.commit("Initial commit");
+
+        let state = Data::new(AppState::new(fixture.config()));
+        let app = init_service(App::new().app_data(state).configure(configure_routes)).await;
+
+        let req = TestRequest::get()
+            .uri("/repo/tree/alpha/master?recursive=true")
+            .to_request();
+        let body: serde_json::Value = call_and_read_body_json(&app, req).await;
+        assert_eq!(body["size"], 8 + 13 + 10);
+        assert_eq!(
+            (body["total"].as_u64(), body["truncated"].as_bool()),
+            (Some(5), Some(false))
+        );
+
+        let entries = body["entries"].as_array().unwrap();
+        let listed: Vec<_> = entries
+            .iter()
+            .map(|e| {
+                (
+                    e["path"].as_str().unwrap(),
+                    e["kind"].as_str().unwrap(),
+                    e["size"].as_u64(),
+                )
+            })
+            .collect();
+        assert_eq!(
+            listed,
+            [
+                ("README.md", "blob", Some(8)),
+                ("src", "tree", Some(23)),
+                ("src/bin", "tree", Some(10)),
+                ("src/bin/run.sh", "executable", Some(10)),
+                ("src/main.rs", "blob", Some(13)),
+            ]
+        );
+        assert_eq!(entries[3]["mode"], "100755");
+
+        let req = TestRequest::get()
+            .uri("/repo/tree/alpha/master/src?recursive=true&limit=2")
+            .to_request();
+        let body: serde_json::Value = call_and_read_body_json(&app, req).await;
+        assert_eq!(body["size"], 23);
+        assert_eq!(body["entries"].as_array().unwrap().len(), 2);
+        assert_eq!(
+            (body["total"].as_u64(), body["truncated"].as_bool()),
+            (Some(3), Some(true))
+        );
+
+        let req = TestRequest::get()
+            .uri("/repo/tree/alpha/master")
+            .to_request();
+        let body: serde_json::Value = call_and_read_body_json(&app, req).await;
+        assert!(body["size"].is_null());
+        assert!(body["entries"][1]["size"].is_null());
+
+        let req = TestRequest::get()
+            .uri("/repo/tree/alpha/master?limit=0")
+            .to_request();
+        let res = call_service(&app, req).await;
+        assert_eq!(res.status(), StatusCode::BAD_REQUEST);
+    }
+
+    #[actix_web::test]
+    async fn test_last_commits_are_found_from_the_revision() {
+        let fixture = Fixture::new();
+        let repo = fixture.repo("alpha");
+        repo.write("README.md", "# Alpha\n")
+            .write("src/main.rs", "fn main() {}\n")
+            .commit("add main");
+        let first = repo.write("src/lib.rs", "\n").commit("add lib");
+        repo.write("src/main.rs", "fn main() { run() }\n")
+            .commit("run from main");
+        let src = repo.git(&["rev-parse", &format!("{first}:src")]);
+
+        let state = Data::new(AppState::new(fixture.config()));
+        let app = init_service(App::new().app_data(state).configure(configure_routes)).await;
+
+        let req = TestRequest::get().uri("/repo/by-branch/alpha").to_request();
+        let body: serde_json::Value = call_and_read_body_json(&app, req).await;
+        assert_eq!(body["objects"][0]["lastCommit"]["msg"], "add main");
+        assert_eq!(body["objects"][1]["lastCommit"]["msg"], "run from main");
+
+        let req = TestRequest::get()
+            .uri("/repo/by-branch/alpha?lastCommits=false")
+            .to_request();
+        let body: serde_json::Value = call_and_read_body_json(&app, req).await;
+        assert!(body["objects"][1]["lastCommit"].is_null());
+
+        // The tree of an older commit, seen from that commit
+        let req = TestRequest::get()
+            .uri(&format!("/repo/by-hash/alpha/{src}?rev={first}"))
+            .to_request();
+        let body: serde_json::Value = call_and_read_body_json(&app, req).await;
+        assert_eq!(body["objects"][0]["name"], "lib.rs");
+        assert_eq!(body["objects"][1]["lastCommit"]["msg"], "add main");
+
+        for _ in 0..2 {
+            let req = TestRequest::get()
+                .uri("/repo/last-commits/alpha/master/src")
+                .to_request();
+            let body: serde_json::Value = call_and_read_body_json(&app, req).await;
+            assert_eq!(body["path"], "src");
+            assert_eq!(body["commits"]["lib.rs"]["msg"], "add lib");
+            assert_eq!(body["commits"]["main.rs"]["msg"], "run from main");
+        }
+
+        // Listings without last commits are cached too
+        for _ in 0..2 {
+            let req = TestRequest::get()
+                .uri(&format!("/repo/by-hash/alpha/{src}?lastCommits=false"))
+                .to_request();
+            let body: serde_json::Value = call_and_read_body_json(&app, req).await;
+            assert!(body["objects"][0]["lastCommit"].is_null());
+        }
+
+        let req = TestRequest::get().uri("/stats/cache").to_request();
+        let body: serde_json::Value = call_and_read_body_json(&app, req).await;
+        assert_eq!(body["lastCommits"]["hits"], 1);
+        assert_eq!(body["lastCommits"]["misses"], 3);
+        assert_eq!(body["trees"]["hits"], 1);
+
+        let req = TestRequest::get()
+            .uri("/repo/last-commits/alpha/master/README.md")
+            .to_request();
+        let res = call_service(&app, req).await;
+        assert_eq!(res.status(), StatusCode::BAD_REQUEST);
+    }
+
+    #[actix_web::test]
+    async fn test_last_commits_of_identical_directories() {
+        let fixture = Fixture::new();
+        let repo = fixture.repo("alpha");
+        repo.write("a/same.txt", "same\n").commit("add a");
+        repo.write("b/same.txt", "same\n").commit("add b");
+
+        let state = Data::new(AppState::new(fixture.config()));
+        let app = init_service(App::new().app_data(state).configure(configure_routes)).await;
+
+        // Both directories are the same tree, changed by different commits
+        for (path, msg) in [("a", "add a"), ("b", "add b"), ("a", "add a")] {
+            let req = TestRequest::get()
+                .uri(&format!("/repo/last-commits/alpha/master/{path}"))
+                .to_request();
+            let body: serde_json::Value = call_and_read_body_json(&app, req).await;
+            assert_eq!(body["path"], path);
+            assert_eq!(body["commits"]["same.txt"]["msg"], msg, "{path}");
         }
     }
 }
@@ -0,0 +1,61 @@
+use crate::{
+    application::AppState,
+    utils::{cache::CacheStats, responses::successful_response},
+};
+use actix_web::{get, web::Data, HttpResponse};
+use serde::Serialize;
+
+#[derive(Serialize, Debug)]
+#[serde(rename_all = "camelCase")]
+/// A model representing the counters of every cache
+pub struct CachesStats {
+    pub objects: CacheStats,
+    pub trees: CacheStats,
+    pub last_commits: CacheStats,
+}
+
+/// Endpoint to monitor how well the caches are doing
+#[get("/cache")]
+pub async fn get_cache_stats(state: Data<AppState>) -> HttpResponse {
+    successful_response(&CachesStats {
+        objects: state.object_cache.stats(),
+        trees: state.tree_cache.stats(),
+        last_commits: state.last_commit_cache.stats(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{application::AppState, routes::config::configure_routes, test_utils::Fixture};
+    use actix_web::{
+        test::{call_and_read_body_json, init_service, TestRequest},
+        web::Data,
+        App,
+    };
+
+    #[actix_web::test]
+    async fn test_objects_are_served_from_cache() {
+        let fixture = Fixture::new();
+        let repo = fixture.repo("alpha");
+        repo.write("a.txt", "a\n").commit("initial commit");
+        let blob = repo.git(&["rev-parse", "HEAD:a.txt"]);
+
+        let state = Data::new(AppState::new(fixture.config()));
+        let app = init_service(App::new().app_data(state).configure(configure_routes)).await;
+
+        // The full and abbreviated hash are the same object
+        for hash in [&blob[..], &blob[..7]] {
+            let req = TestRequest::get()
+                .uri(&format!("/object/by-hash/alpha/{hash}"))
+                .to_request();
+            let body: serde_json::Value = call_and_read_body_json(&app, req).await;
+            assert_eq!(body["content"], "a\n");
+        }
+
+        let req = TestRequest::get().uri("/stats/cache").to_request();
+        let body: serde_json::Value = call_and_read_body_json(&app, req).await;
+        assert_eq!(body["objects"]["hits"], 1);
+        assert_eq!(body["objects"]["misses"], 1);
+        assert_eq!(body["trees"]["hits"], 0);
+    }
+}
@@ -0,0 +1,141 @@
+//! Helpers for building throwaway git repositories in tests.
+
+use crate::config::{Config, ReposConfig};
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    process::Command,
+    sync::atomic::{AtomicUsize, Ordering},
+};
+
+/// Counter making fixture directories unique within the test binary
+static FIXTURE_COUNTER: AtomicUsize = AtomicUsize::new(0);
+
+/// A temporary directory holding bare repositories (served by the
+/// api) and their working copies (used to create commits).
+///
+/// Everything is removed when the fixture is dropped.
+pub struct Fixture {
+    pub root: PathBuf,
+}
+impl Fixture {
+    pub fn new() -> Fixture {
+        let root = std::env::temp_dir().join(format!(
+            "git-api-fixture-{}-{}",
+            std::process::id(),
+            FIXTURE_COUNTER.fetch_add(1, Ordering::Relaxed)
+        ));
+        let _ = fs::remove_dir_all(&root);
+        fs::create_dir_all(root.join("repos")).unwrap();
+        fs::create_dir_all(root.join("work")).unwrap();
+
+        Fixture { root }
+    }
+
+    /// The directory the bare repositories live in
+    pub fn repos_root(&self) -> PathBuf {
+        self.root.join("repos")
+    }
+
+    /// A config serving the repositories of this fixture
+    pub fn config(&self) -> Config {
+        Config {
+            repos: ReposConfig {
+                roots: vec![self.repos_root()],
+            },
+            ..Config::default()
+        }
+    }
+
+    /// Create a new, empty repository named `name`.
+    pub fn repo(&self, name: &str) -> FixtureRepo {
+        let work = self.root.join("work").join(name);
+        let bare = self.repos_root().join(format!("{name}.git"));
+
+        git(
+            &self.root,
+            &["init", "-q", "-b", "master", work.to_str().unwrap()],
+        );
+        git(
+            &self.root,
+            &[
+                "init",
+                "-q",
+                "--bare",
+                "-b",
+                "master",
+                bare.to_str().unwrap(),
+            ],
+        );
+
+        FixtureRepo { work, bare }
+    }
+}
+impl Drop for Fixture {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.root);
+    }
+}
+
+/// A repository created by a [`Fixture`]
+pub struct FixtureRepo {
+    /// The working copy commits are made in
+    pub work: PathBuf,
+    /// The bare repository the api reads from
+    pub bare: PathBuf,
+}
+impl FixtureRepo {
+    /// Write a file (creating parent directories) in the working copy.
+    pub fn write(&self, path: &str, contents: &str) -> &Self {
+        let path = self.work.join(path);
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(path, contents).unwrap();
+        self
+    }
+
+    /// Commit everything in the working copy and push the current
+    /// branch to the bare repository, returning the commit hash.
+    pub fn commit(&self, message: &str) -> String {
+        self.git(&["add", "-A"]);
+        self.git(&["commit", "-q", "--allow-empty", "-m", message]);
+
+        let branch = self.git(&["rev-parse", "--abbrev-ref", "HEAD"]);
+        self.git(&[
+            "push",
+            "-q",
+            "--force",
+            self.bare.to_str().unwrap(),
+            &format!("HEAD:refs/heads/{branch}"),
+        ]);
+
+        self.git(&["rev-parse", "HEAD"])
+    }
+
+    /// Run a git command in the working copy, returning its trimmed stdout.
+    pub fn git(&self, args: &[&str]) -> String {
+        git(&self.work, args)
+    }
+}
+
+/// Run git with a fixed identity, panicking if it fails.
+pub fn git(dir: &Path, args: &[&str]) -> String {
+    let output = Command::new("git")
+        .current_dir(dir)
+        .args(args)
+        .env("GIT_AUTHOR_NAME", "Test Author")
+        .env("GIT_AUTHOR_EMAIL", "author@example.com")
+        .env("GIT_COMMITTER_NAME", "Test Committer")
+        .env("GIT_COMMITTER_EMAIL", "committer@example.com")
+        .env("GIT_CONFIG_NOSYSTEM", "1")
+        .env("GIT_CONFIG_GLOBAL", "/dev/null")
+        .output()
+        .unwrap();
+
+    assert!(
+        output.status.success(),
+        "git {args:?} failed: {}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+
+    String::from_utf8(output.stdout).unwrap().trim().to_string()
+}
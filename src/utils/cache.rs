@@ -0,0 +1,146 @@
+use serde::Serialize;
+use std::{
+    path::Path,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc,
+    },
+};
+
+/// A value that knows roughly how many bytes of memory it takes up.
+pub trait Weigh {
+    fn weigh(&self) -> usize;
+}
+
+impl Weigh for String {
+    fn weigh(&self) -> usize {
+        std::mem::size_of::<String>() + self.len()
+    }
+}
+
+impl<T: Weigh> Weigh for Option<T> {
+    fn weigh(&self) -> usize {
+        self.as_ref().map_or(0, Weigh::weigh)
+    }
+}
+
+impl<T: Weigh> Weigh for Vec<T> {
+    fn weigh(&self) -> usize {
+        std::mem::size_of::<Vec<T>>() + self.iter().map(Weigh::weigh).sum::<usize>()
+    }
+}
+
+/// A concurrent, size bounded cache of immutable values.
+///
+/// Least recently used entries are evicted once the combined weight of
+/// the values goes over the byte budget. Hits and misses are counted
+/// for monitoring.
+pub struct ByteCache<V> {
+    entries: moka::sync::Cache<String, Arc<V>>,
+    hits: AtomicU64,
+    misses: AtomicU64,
+}
+impl<V: Weigh + Send + Sync + 'static> ByteCache<V> {
+    /// Create an empty cache holding at most `budget` bytes of values.
+    pub fn new(budget: usize) -> ByteCache<V> {
+        let entries = moka::sync::Cache::builder()
+            .max_capacity(budget as u64)
+            .weigher(|key: &String, value: &Arc<V>| {
+                (key.len() + value.weigh()).try_into().unwrap_or(u32::MAX)
+            })
+            .build();
+
+        ByteCache {
+            entries,
+            hits: AtomicU64::new(0),
+            misses: AtomicU64::new(0),
+        }
+    }
+
+    /// Try to get a value from the cache.
+    pub fn get(&self, key: &str) -> Option<Arc<V>> {
+        let value = self.entries.get(key);
+        match value {
+            Some(_) => self.hits.fetch_add(1, Ordering::Relaxed),
+            None => self.misses.fetch_add(1, Ordering::Relaxed),
+        };
+
+        value
+    }
+
+    /// Add a value to the cache, returning it shared.
+    pub fn insert(&self, key: String, value: V) -> Arc<V> {
+        let value = Arc::new(value);
+        self.entries.insert(key, value.clone());
+
+        value
+    }
+
+    /// Get a snapshot of how the cache is doing.
+    pub fn stats(&self) -> CacheStats {
+        CacheStats {
+            hits: self.hits.load(Ordering::Relaxed),
+            misses: self.misses.load(Ordering::Relaxed),
+            entries: self.entries.entry_count(),
+            bytes: self.entries.weighted_size(),
+        }
+    }
+}
+
+/// The key of a cached object in a repository, found by its resolved path
+pub fn object_key(repo_path: &Path, oid: &str) -> String {
+    format!("{}/{oid}", repo_path.display())
+}
+
+/// The key of something cached about an object living at a path as seen
+/// from a revision, the same tree being able to live at several paths
+pub fn revision_key(repo_path: &Path, oid: &str, revision: &str, path: &str) -> String {
+    format!("{}@{revision}:{path}", object_key(repo_path, oid))
+}
+
+#[derive(Serialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "camelCase")]
+/// A model representing the counters of a cache
+pub struct CacheStats {
+    pub hits: u64,
+    pub misses: u64,
+    /// The number of entries (eventually consistent)
+    pub entries: u64,
+    /// The combined weight of the entries (eventually consistent)
+    pub bytes: u64,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_hits_and_misses_are_counted() {
+        let cache = ByteCache::new(1024);
+
+        assert!(cache.get("alpha/abc").is_none());
+        cache.insert("alpha/abc".to_string(), "content".to_string());
+        assert_eq!(
+            cache.get("alpha/abc").as_deref(),
+            Some(&"content".to_string())
+        );
+        assert!(cache.get("beta/abc").is_none());
+
+        let stats = cache.stats();
+        assert_eq!((stats.hits, stats.misses), (1, 2));
+    }
+
+    #[test]
+    fn test_cache_stays_within_its_budget() {
+        let cache = ByteCache::new(4096);
+
+        for i in 0..100 {
+            cache.insert(format!("alpha/{i}"), "x".repeat(256));
+        }
+        cache.entries.run_pending_tasks();
+
+        let stats = cache.stats();
+        assert!(stats.bytes <= 4096, "{stats:?}");
+        assert!(stats.entries < 100, "{stats:?}");
+    }
+}
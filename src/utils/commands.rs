@@ -1,69 +1,320 @@
-use crate::application::GitApiError;
+use crate::{
+    application::{GitApiError, GitCommandFailure},
+    config::GitConfig,
+    utils::deadline,
+};
 use base64::{engine::general_purpose, Engine as _};
 use std::{
-    env,
-    process::{Command, Stdio},
+    path::Path,
+    process::{Output, Stdio},
+    sync::OnceLock,
+    time::{Duration, Instant},
+};
+use tokio::{
+    io::{AsyncBufReadExt, AsyncReadExt, BufReader},
+    process::Command,
+    sync::{Semaphore, SemaphorePermit},
+    time::timeout,
 };
 
-/// Try to run git commands on the server
-pub fn run_git_command(args: &[&str], is_binary: bool) -> Result<String, GitApiError> {
-    // Run the command and store the output
-    let output = Command::new("git")
-        .args(args)
-        .stdout(Stdio::piped())
-        .stderr(Stdio::piped())
-        .spawn()?
-        .wait_with_output()?;
+/// Limits applied to every git process the server spawns
+struct GitLimits {
+    /// Bounds how many git processes run at once
+    semaphore: Semaphore,
+    /// How long a single git command may run before it's killed
+    command_timeout: Duration,
+}
+impl From<&GitConfig> for GitLimits {
+    fn from(config: &GitConfig) -> Self {
+        GitLimits {
+            semaphore: Semaphore::new(config.max_concurrent_commands),
+            command_timeout: config.command_timeout(),
+        }
+    }
+}
+
+static GIT_LIMITS: OnceLock<GitLimits> = OnceLock::new();
+
+/// Configure the limits applied to git processes.
+///
+/// Must be called once at startup before any git command runs,
+/// otherwise the default limits are used.
+pub fn configure_git_limits(config: &GitConfig) {
+    if GIT_LIMITS.set(GitLimits::from(config)).is_err() {
+        log::warn!("Git limits were already configured, ignoring new configuration");
+    }
+}
+
+fn git_limits() -> &'static GitLimits {
+    GIT_LIMITS.get_or_init(|| GitLimits::from(&GitConfig::default()))
+}
+
+/// Try to run git commands on the server against a specific repository.
+///
+/// The repository is passed to git with `-C` instead of changing the
+/// process wide current directory, so concurrent requests never run
+/// git in each others repositories.
+///
+/// Waits for a free slot if too many git processes are already running,
+/// and kills the process if it runs longer than the command timeout or
+/// the remaining budget of the current request.
+pub async fn run_git_command(
+    repo_path: &Path,
+    args: &[&str],
+    is_binary: bool,
+) -> Result<String, GitApiError> {
+    let stdout = run_git_command_bytes(repo_path, args).await?;
+
+    if is_binary {
+        // Encode the binary output into base-64 bytes
+        Ok(general_purpose::STANDARD_NO_PAD.encode(stdout))
+    } else {
+        Ok(String::from_utf8(stdout)?)
+    }
+}
+
+/// Try to run git commands on the server against a specific repository,
+/// returning exactly what git wrote to stdout.
+///
+/// The same limits as [`run_git_command`] apply.
+pub async fn run_git_command_bytes(
+    repo_path: &Path,
+    args: &[&str],
+) -> Result<Vec<u8>, GitApiError> {
+    let output = run_git(repo_path, args).await?;
 
     if output.status.success() {
-        if is_binary {
-            // Encode the binary output into base-64 bytes
-            Ok(general_purpose::STANDARD_NO_PAD.encode(output.stdout))
-        } else {
-            Ok(String::from_utf8(output.stdout)?)
-        }
+        Ok(output.stdout)
     } else {
-        let stderr = String::from_utf8_lossy(&output.stderr);
-        Err(GitApiError::CommandFailed)
+        Err(GitApiError::from(GitCommandFailure {
+            args: argv(repo_path, args),
+            status: output.status.code(),
+            stderr: String::from_utf8_lossy(&output.stderr).trim().to_string(),
+        }))
     }
 }
 
-/// Try to change the current directory
-pub fn change_directory(path: &str) -> Result<(), GitApiError> {
-    Ok(env::set_current_dir(path)?)
+/// Try to run git commands on the server against a specific repository,
+/// returning what git wrote to stdout along with its exit status, for
+/// commands that report results (not failures) through the status.
+///
+/// The same limits as [`run_git_command`] apply, and failures to run
+/// git at all are still errors.
+pub async fn run_git_command_with_status(
+    repo_path: &Path,
+    args: &[&str],
+) -> Result<(Option<i32>, String), GitApiError> {
+    let output = run_git(repo_path, args).await?;
+
+    Ok((output.status.code(), String::from_utf8(output.stdout)?))
 }
 
-/// Try to derive a filename from a hash.
+/// Try to run git commands on the server against a specific repository,
+/// handing what git writes to stdout to `consume` a record at a time (the
+/// records being separated by `separator`) for as long as it asks for
+/// more by returning `true`. Git is killed once it doesn't, so commands
+/// walking history stop as soon as what they were run for is found.
 ///
-/// Runs a git command to get a reversed git list, piped into a
-/// grep command to find objects/files that match the given hash.
-pub fn get_filename_from_hash(hash: &str) -> Result<String, GitApiError> {
-    let mut git_command = Command::new("git")
-        .args(["rev-list", "--objects", "--all"])
-        .stdout(Stdio::piped())
-        .spawn()?;
+/// The same limits as [`run_git_command`] apply.
+pub async fn stream_git_command<F>(
+    repo_path: &Path,
+    args: &[&str],
+    separator: u8,
+    mut consume: F,
+) -> Result<(), GitApiError>
+where
+    F: FnMut(&str) -> bool,
+{
+    let slot = acquire_git_slot().await?;
 
-    let mut grep_command = Command::new("grep")
-        .arg(hash)
-        .stdin(Stdio::piped())
+    let mut child = Command::new("git")
+        .arg("-C")
+        .arg(repo_path)
+        .args(args)
+        .stdin(Stdio::null())
         .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .kill_on_drop(true)
         .spawn()?;
+    let (Some(stdout), Some(mut stderr)) = (child.stdout.take(), child.stderr.take()) else {
+        return Err(GitApiError::Backend(
+            "Git's output wasn't piped".to_string(),
+        ));
+    };
+
+    let stream = async {
+        // Stderr is read alongside so git never blocks on writing it
+        let read_stderr = async {
+            let mut buffer = Vec::new();
+            stderr.read_to_end(&mut buffer).await.map(|_| buffer)
+        };
+        let read_stdout = async {
+            let mut stdout = BufReader::new(stdout);
+            let mut record = Vec::new();
+            let finished = loop {
+                match stdout.read_until(separator, &mut record).await {
+                    Ok(0) => break Ok(true),
+                    Ok(_) => {}
+                    Err(e) => break Err(GitApiError::from(e)),
+                }
+                if record.last() == Some(&separator) {
+                    record.pop();
+                }
+                if !consume(&String::from_utf8_lossy(&record)) {
+                    break Ok(false);
+                }
+                record.clear();
+            };
+
+            // However reading stopped early, git is killed so stderr is
+            // closed too and reading it is over as well
+            if !matches!(finished, Ok(true)) {
+                child.start_kill()?;
+            }
+            finished
+        };
+        let (finished, stderr) = tokio::join!(read_stdout, read_stderr);
+        if !finished? {
+            return Ok(());
+        }
 
-    if let Some(ref mut git_stdout) = git_command.stdout {
-        if let Some(ref mut grep_stdin) = grep_command.stdin {
-            std::io::copy(git_stdout, grep_stdin)?;
+        let status = child.wait().await?;
+        if status.success() {
+            Ok(())
+        } else {
+            Err(GitApiError::from(GitCommandFailure {
+                args: argv(repo_path, args),
+                status: status.code(),
+                stderr: String::from_utf8_lossy(&stderr?).trim().to_string(),
+            }))
         }
+    };
+
+    // Dropping the future on timeout drops the child, which kills it
+    match timeout(slot.time_limit, stream).await {
+        Ok(result) => result,
+        Err(_) => Err(GitApiError::CommandTimedOut(argv(repo_path, args))),
+    }
+}
+
+/// The arguments git is run with, for error messages
+fn argv(repo_path: &Path, args: &[&str]) -> Vec<String> {
+    ["-C".to_string(), repo_path.display().to_string()]
+        .into_iter()
+        .chain(args.iter().map(|arg| arg.to_string()))
+        .collect()
+}
+
+/// A slot to do git work in, taken from the pool git processes run in
+/// and given back when dropped
+pub struct GitSlot {
+    _permit: SemaphorePermit<'static>,
+    /// How long the work may run, within the current request's budget
+    pub time_limit: Duration,
+}
+
+/// Wait for a free slot to do git work in, for work done without
+/// spawning git (like reading repositories in-process) that must still
+/// stay within the limits git processes run in.
+pub async fn acquire_git_slot() -> Result<GitSlot, GitApiError> {
+    let limits = git_limits();
+    // Never wait or run longer than the current request is allowed to
+    let budget = deadline::remaining();
+    if budget.is_some_and(|budget| budget.is_zero()) {
+        return Err(GitApiError::RequestTimedOut);
+    }
+    let time_limit = budget.map_or(limits.command_timeout, |budget| {
+        budget.min(limits.command_timeout)
+    });
+
+    // Wait for a free slot, giving up if the server is too busy
+    let started = Instant::now();
+    let permit = match timeout(time_limit, limits.semaphore.acquire()).await {
+        Ok(permit) => permit.map_err(|_| GitApiError::Overloaded)?,
+        Err(_) => return Err(GitApiError::Overloaded),
+    };
+
+    // The time spent waiting counts against the limit too
+    let time_limit = time_limit.saturating_sub(started.elapsed());
+    if time_limit.is_zero() {
+        return Err(GitApiError::Overloaded);
     }
 
-    let grep_output = grep_command.wait_with_output()?;
+    Ok(GitSlot {
+        _permit: permit,
+        time_limit,
+    })
+}
+
+/// Run git within the limits, whatever its exit status.
+async fn run_git(repo_path: &Path, args: &[&str]) -> Result<Output, GitApiError> {
+    let slot = acquire_git_slot().await?;
+    let command_timeout = slot.time_limit;
+
+    // Run the command and store the output
+    let child = Command::new("git")
+        .arg("-C")
+        .arg(repo_path)
+        .args(args)
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .kill_on_drop(true)
+        .spawn()?;
+
+    // Dropping the future on timeout drops the child, which kills it
+    let output = match timeout(command_timeout, child.wait_with_output()).await {
+        Ok(output) => output?,
+        Err(_) => return Err(GitApiError::CommandTimedOut(argv(repo_path, args))),
+    };
+
+    Ok(output)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::Fixture;
+
+    #[tokio::test]
+    async fn test_streaming_stops_when_asked() {
+        let fixture = Fixture::new();
+        let repo = fixture.repo("alpha");
+        for i in 0..3 {
+            repo.write("a.txt", &i.to_string())
+                .commit(&format!("commit {i}"));
+        }
+        let args = ["log", "--format=%x1e%s", "master"];
 
-    git_command.wait()?;
+        let mut records = Vec::new();
+        stream_git_command(&repo.bare, &args, b'\x1e', |record| {
+            if !record.is_empty() {
+                records.push(record.trim().to_string());
+            }
+            records.len() < 2
+        })
+        .await
+        .unwrap();
+        assert_eq!(records, ["commit 2", "commit 1"]);
 
-    let grep_output_str = String::from_utf8(grep_output.stdout)?;
+        // Output that isn't UTF-8 is read anyway
+        std::fs::write(repo.work.join("bin"), [b'a', 0xff, b'\n']).unwrap();
+        repo.commit("binary");
+        let mut lines = Vec::new();
+        stream_git_command(
+            &repo.bare,
+            &["cat-file", "-p", "master:bin"],
+            b'\n',
+            |line| {
+                lines.push(line.to_string());
+                true
+            },
+        )
+        .await
+        .unwrap();
+        assert_eq!(lines, ["a\u{fffd}"]);
 
-    grep_output_str
-        .split_whitespace()
-        .last()
-        .map(|s| s.to_string())
-        .ok_or(GitApiError::NoLastElement)
+        let result = stream_git_command(&repo.bare, &["log", "nope"], b'\x1e', |_| true).await;
+        assert!(matches!(result, Err(GitApiError::AmbiguousArgument(_))));
+    }
 }
@@ -1,4 +1,7 @@
-use crate::utils::dates::parse_date_to_string;
+use crate::utils::{
+    cache::Weigh,
+    dates::{parse_date_to_string, parse_date_to_timestamp},
+};
 use serde::Serialize;
 
 #[derive(Serialize, Debug, Clone)]
@@ -9,6 +12,25 @@ pub struct Commit {
     pub author_email: String,
     pub date: String,
     pub msg: String,
+    /// The date as a unix timestamp, for sorting and filtering
+    #[serde(skip)]
+    pub timestamp: i64,
+}
+impl Commit {
+    /// Create a [`Commit`] from its raw parts.
+    ///
+    /// NOTE: The date is ALWAYS in the strict ISO 8601
+    /// format (`git log --date=iso-strict`).
+    pub fn new(hash: &str, author: &str, author_email: &str, date: &str, message: &str) -> Self {
+        Commit {
+            hash: hash.get(..6).unwrap_or(hash).trim().to_string(),
+            author: author.trim().to_string(),
+            author_email: author_email.trim().to_string(),
+            date: parse_date_to_string(date.trim().to_string()),
+            msg: message.lines().next().unwrap_or("").trim().to_string(),
+            timestamp: parse_date_to_timestamp(date.trim()),
+        }
+    }
 }
 impl From<&str> for Commit {
     /// Parse out [`Commit`]'s from a string slice.
@@ -17,26 +39,21 @@ impl From<&str> for Commit {
     /// output of the `git log` command to fetch commits.
     fn from(log_entry: &str) -> Self {
         let mut parts = log_entry.split('\x1f');
-        let hash = parts.next().unwrap_or("")[..6].trim().to_string();
-        let author = parts.next().unwrap_or("").trim().to_string();
-        let author_email = parts.next().unwrap_or("").trim().to_string();
-        let date = parse_date_to_string(parts.next().unwrap_or("").trim().to_string());
-        let msg = parts
-            .skip(1)
-            .collect::<Vec<_>>()
-            .join("\x1f")
-            .lines()
-            .take(1)
-            .collect::<String>()
-            .trim()
-            .to_string();
+        let hash = parts.next().unwrap_or("");
+        let author = parts.next().unwrap_or("");
+        let author_email = parts.next().unwrap_or("");
+        let date = parts.next().unwrap_or("");
+        let msg = parts.skip(1).collect::<Vec<_>>().join("\x1f");
 
-        Commit {
-            hash,
-            author,
-            author_email,
-            date,
-            msg,
-        }
+        Commit::new(hash, author, author_email, date, &msg)
+    }
+}
+impl Weigh for Commit {
+    fn weigh(&self) -> usize {
+        self.hash.weigh()
+            + self.author.weigh()
+            + self.author_email.weigh()
+            + self.date.weigh()
+            + self.msg.weigh()
     }
 }
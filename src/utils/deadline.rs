@@ -0,0 +1,101 @@
+use crate::application::GitApiError;
+use actix_web::{
+    dev::{Service, ServiceRequest, ServiceResponse},
+    Error,
+};
+use std::{
+    future::Future,
+    time::{Duration, Instant},
+};
+
+/// How long past its budget a request may run before it's dropped
+const GRACE_PERIOD: Duration = Duration::from_millis(500);
+
+tokio::task_local! {
+    /// The instant the request currently being handled has to finish by
+    static DEADLINE: Instant;
+}
+
+/// Get how much time the request currently being handled has left.
+///
+/// Returns `None` outside of a request (no budget applies), and a zero
+/// duration once the budget is used up.
+pub fn remaining() -> Option<Duration> {
+    DEADLINE
+        .try_with(|deadline| deadline.saturating_duration_since(Instant::now()))
+        .ok()
+}
+
+/// Middleware giving every request a time budget.
+///
+/// The deadline is made available to git commands through [`remaining`]
+/// so they never outlive the request and the handler fails with a
+/// `504 Gateway Timeout` once it's spent. As a last resort, a handler
+/// still running after the budget (plus a grace period) is dropped,
+/// killing any git processes it spawned.
+pub fn enforce<S, B>(
+    budget: Duration,
+    req: ServiceRequest,
+    srv: &S,
+) -> impl Future<Output = Result<ServiceResponse<B>, Error>>
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error>,
+{
+    let deadline = Instant::now() + budget;
+    let fut = DEADLINE.sync_scope(deadline, || srv.call(req));
+
+    DEADLINE.scope(deadline, async move {
+        match tokio::time::timeout(budget + GRACE_PERIOD, fut).await {
+            Ok(res) => res,
+            Err(_) => Err(GitApiError::RequestTimedOut.into()),
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::commands::run_git_command;
+    use actix_web::{
+        http::StatusCode,
+        test::{init_service, TestRequest},
+        web, App, HttpResponse,
+    };
+    use std::path::Path;
+
+    #[actix_web::test]
+    async fn test_slow_request_times_out() {
+        let app = init_service(
+            App::new()
+                .wrap_fn(|req, srv| enforce(Duration::from_millis(50), req, srv))
+                .route(
+                    "/slow",
+                    web::get().to(|| async {
+                        tokio::time::sleep(Duration::from_secs(5)).await;
+                        HttpResponse::Ok().finish()
+                    }),
+                ),
+        )
+        .await;
+
+        let err = app
+            .call(TestRequest::get().uri("/slow").to_request())
+            .await
+            .expect_err("request should have timed out");
+        assert_eq!(
+            err.as_response_error().status_code(),
+            StatusCode::GATEWAY_TIMEOUT
+        );
+    }
+
+    #[actix_web::test]
+    async fn test_git_commands_respect_the_deadline() {
+        let result = DEADLINE
+            .scope(Instant::now(), async {
+                run_git_command(Path::new("."), &["--version"], false).await
+            })
+            .await;
+
+        assert!(matches!(result, Err(GitApiError::RequestTimedOut)));
+    }
+}
@@ -1,5 +1,9 @@
+pub mod cache;
 pub mod commands;
 pub mod commits;
 pub mod dates;
+pub mod deadline;
+pub mod pagination;
+pub mod request_id;
 pub mod responses;
 pub mod validation;
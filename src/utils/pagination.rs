@@ -0,0 +1,119 @@
+use crate::application::GitApiError;
+use serde::{Deserialize, Serialize};
+
+/// How many items are on a page when it's not asked for
+pub const DEFAULT_PER_PAGE: usize = 50;
+/// The most items a single page may hold
+pub const MAX_PER_PAGE: usize = 100;
+
+#[derive(Deserialize, Debug, Default)]
+#[serde(default, rename_all = "camelCase", deny_unknown_fields)]
+/// The query string of endpoints only taking a page
+pub struct PageQuery {
+    /// The page to get, starting at 1
+    pub page: Option<usize>,
+    pub per_page: Option<usize>,
+}
+
+#[derive(Serialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "camelCase")]
+/// A model representing where a page is in a list
+pub struct PageInfo {
+    /// How many items there are, on every page
+    pub total: usize,
+    /// The page, starting at 1
+    pub page: usize,
+    pub per_page: usize,
+}
+
+#[derive(Serialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "camelCase")]
+/// A model representing where a page is in a list too costly to count
+pub struct OpenPageInfo {
+    /// The page, starting at 1
+    pub page: usize,
+    pub per_page: usize,
+    /// Whether there are items past the page
+    pub has_more: bool,
+}
+
+/// Try to get the page (starting at 1) and page size asked for, falling
+/// back on the first page of [`DEFAULT_PER_PAGE`] items.
+pub fn page_bounds(
+    page: Option<usize>,
+    per_page: Option<usize>,
+) -> Result<(usize, usize), GitApiError> {
+    let page = page.unwrap_or(1);
+    let per_page = per_page.unwrap_or(DEFAULT_PER_PAGE);
+    if page == 0 {
+        return Err(GitApiError::InvalidInput(
+            "Page numbers start at 1".to_string(),
+        ));
+    }
+    if per_page == 0 || per_page > MAX_PER_PAGE {
+        return Err(GitApiError::InvalidInput(format!(
+            "Page size must be between 1 and {MAX_PER_PAGE}"
+        )));
+    }
+
+    Ok((page, per_page))
+}
+
+/// Try to cut a page out of a list of items, the page starting at 1.
+pub fn paginate<T>(
+    items: Vec<T>,
+    page: Option<usize>,
+    per_page: Option<usize>,
+) -> Result<(Vec<T>, PageInfo), GitApiError> {
+    let (page, per_page) = page_bounds(page, per_page)?;
+
+    let total = items.len();
+    let items = items
+        .into_iter()
+        .skip((page - 1).saturating_mul(per_page))
+        .take(per_page)
+        .collect();
+
+    Ok((
+        items,
+        PageInfo {
+            total,
+            page,
+            per_page,
+        },
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_paginate() {
+        let (items, info) = paginate((1..=5).collect(), Some(2), Some(2)).unwrap();
+        assert_eq!(items, [3, 4]);
+        assert_eq!(
+            info,
+            PageInfo {
+                total: 5,
+                page: 2,
+                per_page: 2
+            }
+        );
+
+        let (items, _) = paginate((1..=5).collect(), Some(4), Some(2)).unwrap();
+        assert!(items.is_empty());
+
+        let (items, info) = paginate((1..=5).collect(), None, None).unwrap();
+        assert_eq!(items.len(), 5);
+        assert_eq!((info.page, info.per_page), (1, DEFAULT_PER_PAGE));
+    }
+
+    #[test]
+    fn test_invalid_pages() {
+        for (page, per_page) in [(Some(0), None), (None, Some(0)), (None, Some(1000))] {
+            let result = paginate(vec![1], page, per_page);
+            assert!(matches!(result, Err(GitApiError::InvalidInput(_))));
+        }
+    }
+}
@@ -0,0 +1,79 @@
+use actix_web::{
+    dev::{Service, ServiceRequest, ServiceResponse},
+    http::header::{HeaderName, HeaderValue},
+    Error,
+};
+use std::{
+    future::Future,
+    sync::atomic::{AtomicU64, Ordering},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+/// The header used to receive and echo back a request id
+pub const REQUEST_ID_HEADER: &str = "x-request-id";
+
+/// Counter making request ids unique within this process
+static REQUEST_COUNTER: AtomicU64 = AtomicU64::new(0);
+
+tokio::task_local! {
+    /// The id of the request currently being handled
+    static REQUEST_ID: String;
+}
+
+/// Get the id of the request currently being handled, if any.
+pub fn current() -> Option<String> {
+    REQUEST_ID.try_with(|id| id.clone()).ok()
+}
+
+/// Generate a new request id.
+///
+/// The id is the unix timestamp in milliseconds combined with
+/// a process wide counter, both hex encoded.
+fn generate() -> String {
+    let millis = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_millis())
+        .unwrap_or_default();
+    let count = REQUEST_COUNTER.fetch_add(1, Ordering::Relaxed);
+
+    format!("{millis:x}-{count:06x}")
+}
+
+/// Middleware assigning every request an id.
+///
+/// Reuses the id sent by the client in the `X-Request-Id` header when it's
+/// sane, makes it available to the handler through [`current`] and echoes
+/// it back in the response headers.
+pub fn assign<S, B>(
+    req: ServiceRequest,
+    srv: &S,
+) -> impl Future<Output = Result<ServiceResponse<B>, Error>>
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error>,
+{
+    let id = req
+        .headers()
+        .get(REQUEST_ID_HEADER)
+        .and_then(|value| value.to_str().ok())
+        .filter(|value| {
+            !value.is_empty()
+                && value.len() <= 64
+                && value
+                    .chars()
+                    .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+        })
+        .map(|value| value.to_string())
+        .unwrap_or_else(generate);
+
+    let fut = REQUEST_ID.sync_scope(id.clone(), || srv.call(req));
+
+    REQUEST_ID.scope(id.clone(), async move {
+        let mut res = fut.await?;
+        if let Ok(value) = HeaderValue::from_str(&id) {
+            res.headers_mut()
+                .insert(HeaderName::from_static(REQUEST_ID_HEADER), value);
+        }
+
+        Ok(res)
+    })
+}
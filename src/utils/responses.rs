@@ -8,7 +8,3 @@ pub fn successful_response<T: Serialize>(obj: &T) -> HttpResponse {
         Err(_) => HttpResponse::InternalServerError().body("Failed to serialize to JSON"),
     }
 }
-
-pub fn internal_server_error() -> HttpResponse {
-    HttpResponse::InternalServerError().body("Internal Server Error")
-}
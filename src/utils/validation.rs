@@ -87,11 +87,51 @@ pub fn validate_hash(hash: &str) -> Result<&str, GitApiError> {
     Ok(hash)
 }
 
+/// Validate a path to a file inside a repository, relative to its root.
+///
+/// Paths can't start with `:`, which git reads as pathspec magic, but
+/// may still hold wildcards (`*`, `?`, `[`) as file names can too. Git
+/// must be run with `--literal-pathspecs` wherever a path is passed as
+/// a pathspec, so they match that file only.
+pub fn validate_file_path(path: &str) -> Result<&str, GitApiError> {
+    if path.is_empty() {
+        return Err(GitApiError::InvalidInput(
+            "File path cannot be empty".to_string(),
+        ));
+    }
+
+    if path.len() > 4096 {
+        return Err(GitApiError::InvalidInput("File path too long".to_string()));
+    }
+
+    if path.starts_with('/')
+        || path.starts_with(':')
+        || path
+            .split('/')
+            .any(|component| component.is_empty() || component == "." || component == "..")
+    {
+        return Err(GitApiError::InvalidInput(
+            "Invalid file path format".to_string(),
+        ));
+    }
+
+    if path.chars().any(|c| c.is_control() || c == '\\') {
+        return Err(GitApiError::InvalidInput(
+            "File path contains invalid characters".to_string(),
+        ));
+    }
+
+    Ok(path)
+}
+
 /// Validate and construct a safe repository path
-pub fn validate_repo_path(base_path: &str, repo_name: &str) -> Result<PathBuf, GitApiError> {
+pub fn validate_repo_path(
+    base_path: impl AsRef<Path>,
+    repo_name: &str,
+) -> Result<PathBuf, GitApiError> {
     validate_repo_name(repo_name)?;
 
-    let base = Path::new(base_path);
+    let base = base_path.as_ref();
     let repo_path = base.join(format!("{}.git", repo_name));
 
     // Ensure the constructed path is still under the base directory
@@ -116,6 +156,20 @@ pub fn validate_repo_path(base_path: &str, repo_name: &str) -> Result<PathBuf, G
     }
 }
 
+/// Validate and construct a safe path to a repository that exists on the server
+pub fn validate_existing_repo_path(
+    base_path: impl AsRef<Path>,
+    repo_name: &str,
+) -> Result<PathBuf, GitApiError> {
+    let repo_path = validate_repo_path(base_path, repo_name)?;
+
+    if repo_path.is_dir() {
+        Ok(repo_path)
+    } else {
+        Err(GitApiError::RepoNotFound(repo_name.to_string()))
+    }
+}
+
 /// Normalize a path by resolving `..` and `.` components without requiring the path to exist.
 fn normalize_path(path: &Path) -> PathBuf {
     let mut components = Vec::new();
@@ -178,6 +232,24 @@ mod tests {
         assert!(validate_hash("").is_err());
     }
 
+    #[test]
+    fn test_validate_file_path() {
+        assert!(validate_file_path("README.md").is_ok());
+        assert!(validate_file_path("src/main.rs").is_ok());
+        assert!(validate_file_path("docs/copy of readme.md").is_ok());
+        assert!(validate_file_path("-file").is_ok());
+        assert!(validate_file_path("src/*.rs").is_ok());
+        assert!(validate_file_path("src/:main.rs").is_ok());
+
+        assert!(validate_file_path("").is_err());
+        assert!(validate_file_path("/etc/passwd").is_err());
+        assert!(validate_file_path(":(glob)**").is_err());
+        assert!(validate_file_path("src/../../etc").is_err());
+        assert!(validate_file_path("src//main.rs").is_err());
+        assert!(validate_file_path("src/").is_err());
+        assert!(validate_file_path("a\nb").is_err());
+    }
+
     #[test]
     fn test_validate_repo_path() {
         let base = "/tmp/repos";
@@ -190,6 +262,18 @@ mod tests {
         assert!(validate_repo_path(base, "repo/../etc").is_err());
     }
 
+    #[test]
+    fn test_validate_existing_repo_path() {
+        let base = std::env::temp_dir().join("git-api-validation-test");
+        std::fs::create_dir_all(base.join("exists.git")).unwrap();
+
+        assert!(validate_existing_repo_path(&base, "exists").is_ok());
+        assert!(matches!(
+            validate_existing_repo_path(&base, "missing"),
+            Err(GitApiError::RepoNotFound(_))
+        ));
+    }
+
     #[test]
     fn test_normalize_path() {
         assert_eq!(normalize_path(Path::new("/a/b/c")), PathBuf::from("/a/b/c"));